@@ -0,0 +1,29 @@
+use tree_playground::{
+    avl::AvlSet,
+    concurrent::{ConcurrentSet, SnapshotSet},
+    persistent::PersistentAvlSet,
+    play_concurrent_numbers,
+    skiplist::SkipList,
+};
+
+fn main() {
+    println!("{}", "=".repeat(80));
+    println!("START PLAY WITH CONCURRENT TREES");
+    for n in [10000_usize, 100000, 1000000] {
+        for (readers, writers) in [(1, 1), (4, 1), (4, 4)] {
+            println!("AvlSet behind RwLock, n = {n}");
+            play_concurrent_numbers(&ConcurrentSet::new(AvlSet::default()), n, readers, writers);
+            println!("PersistentAvlSet with snapshots, n = {n}");
+            play_concurrent_numbers(
+                &SnapshotSet::new(PersistentAvlSet::default()),
+                n,
+                readers,
+                writers,
+            );
+            println!("Lock-free SkipList, n = {n}");
+            play_concurrent_numbers(&SkipList::default(), n, readers, writers);
+            println!();
+        }
+        println!("{}", "-".repeat(80));
+    }
+}
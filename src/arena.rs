@@ -0,0 +1,519 @@
+use std::{
+    cmp::{max, Ordering},
+    mem::replace,
+    ops::{Index, IndexMut},
+};
+
+use rand::Rng;
+
+use crate::{
+    memory::HeapSize,
+    viz::{priority_label, Shape},
+    Tree,
+};
+
+// Index of a missing child.
+const NIL: u32 = u32::MAX;
+
+// `rank` is the subtree height in `ArenaAvlSet` and the heap priority in
+// `ArenaTreapSet`.
+#[derive(Debug, Clone, PartialEq)]
+struct Node {
+    value: i64,
+    rank: u32,
+    left: u32,
+    right: u32,
+}
+
+impl Node {
+    pub fn new(value: i64, rank: u32) -> Self {
+        Self {
+            value,
+            rank,
+            left: NIL,
+            right: NIL,
+        }
+    }
+}
+
+/// Nodes of one tree stored side by side in a `Vec`. Slots of removed nodes
+/// go to a free list and are reused by later inserts.
+#[derive(Debug, Clone, Default)]
+struct Arena {
+    nodes: Vec<Node>,
+    free: Vec<u32>,
+}
+
+impl Arena {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            nodes: Vec::with_capacity(capacity),
+            free: vec![],
+        }
+    }
+
+    pub fn alloc(&mut self, node: Node) -> u32 {
+        if let Some(index) = self.free.pop() {
+            self[index] = node;
+            return index;
+        }
+        let index = u32::try_from(self.nodes.len())
+            .ok()
+            .filter(|index| *index != NIL)
+            .expect("arena is limited to u32::MAX - 1 nodes");
+        self.nodes.push(node);
+        index
+    }
+
+    pub fn release(&mut self, index: u32) -> i64 {
+        self.free.push(index);
+        self[index].value
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len() - self.free.len()
+    }
+}
+
+impl Index<u32> for Arena {
+    type Output = Node;
+
+    fn index(&self, index: u32) -> &Self::Output {
+        &self.nodes[index as usize]
+    }
+}
+
+impl IndexMut<u32> for Arena {
+    fn index_mut(&mut self, index: u32) -> &mut Self::Output {
+        &mut self.nodes[index as usize]
+    }
+}
+
+fn search(arena: &Arena, root: u32, value: i64) -> bool {
+    let mut current = root;
+    while current != NIL {
+        let node = &arena[current];
+        match node.value.cmp(&value) {
+            Ordering::Less => current = node.right,
+            Ordering::Equal => return true,
+            Ordering::Greater => current = node.left,
+        }
+    }
+    false
+}
+
+fn shape(arena: &Arena, index: u32, label: &impl Fn(&Node) -> String) -> Option<Shape> {
+    (index != NIL).then(|| {
+        let node = &arena[index];
+        Shape::new(
+            node.value,
+            label(node),
+            shape(arena, node.left, label),
+            shape(arena, node.right, label),
+        )
+    })
+}
+
+/// AVL tree with nodes in an arena instead of separate boxes.
+#[derive(Debug, Clone)]
+pub struct ArenaAvlSet {
+    arena: Arena,
+    root: u32,
+}
+
+impl Default for ArenaAvlSet {
+    fn default() -> Self {
+        Self::with_capacity(0)
+    }
+}
+
+impl ArenaAvlSet {
+    /// Room for `capacity` values before the arena has to grow.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            arena: Arena::with_capacity(capacity),
+            root: NIL,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.arena.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root == NIL
+    }
+
+    pub fn iter(&self) -> ArenaIter<'_> {
+        ArenaIter {
+            arena: &self.arena,
+            prev_nodes: vec![],
+            current: self.root,
+        }
+    }
+
+    fn height(&self, index: u32) -> u32 {
+        if index == NIL {
+            0
+        } else {
+            self.arena[index].rank
+        }
+    }
+
+    fn update_height(&mut self, index: u32) {
+        let node = &self.arena[index];
+        let height = 1 + max(self.height(node.left), self.height(node.right));
+        self.arena[index].rank = height;
+    }
+
+    fn balance_factor(&self, index: u32) -> i64 {
+        let node = &self.arena[index];
+        self.height(node.left) as i64 - self.height(node.right) as i64
+    }
+
+    fn rotate_left(&mut self, index: u32) -> u32 {
+        let right = self.arena[index].right;
+        self.arena[index].right = self.arena[right].left;
+        self.arena[right].left = index;
+        self.update_height(index);
+        self.update_height(right);
+        right
+    }
+
+    fn rotate_right(&mut self, index: u32) -> u32 {
+        let left = self.arena[index].left;
+        self.arena[index].left = self.arena[left].right;
+        self.arena[left].right = index;
+        self.update_height(index);
+        self.update_height(left);
+        left
+    }
+
+    // Returns the index of the subtree root after rotations.
+    fn rebalance(&mut self, index: u32) -> u32 {
+        self.update_height(index);
+        match self.balance_factor(index) {
+            -2 => {
+                let right = self.arena[index].right;
+                if self.balance_factor(right) == 1 {
+                    self.arena[index].right = self.rotate_right(right);
+                }
+                self.rotate_left(index)
+            }
+            2 => {
+                let left = self.arena[index].left;
+                if self.balance_factor(left) == -1 {
+                    self.arena[index].left = self.rotate_left(left);
+                }
+                self.rotate_right(index)
+            }
+            _ => index,
+        }
+    }
+
+    fn insert_into(&mut self, index: u32, value: i64) -> (u32, bool) {
+        if index == NIL {
+            return (self.arena.alloc(Node::new(value, 1)), true);
+        }
+        let inserted = match self.arena[index].value.cmp(&value) {
+            Ordering::Less => {
+                let (right, inserted) = self.insert_into(self.arena[index].right, value);
+                self.arena[index].right = right;
+                inserted
+            }
+            Ordering::Equal => false,
+            Ordering::Greater => {
+                let (left, inserted) = self.insert_into(self.arena[index].left, value);
+                self.arena[index].left = left;
+                inserted
+            }
+        };
+        if inserted {
+            (self.rebalance(index), true)
+        } else {
+            (index, false)
+        }
+    }
+
+    fn remove_from(&mut self, index: u32, value: i64) -> (u32, Option<i64>) {
+        if index == NIL {
+            return (NIL, None);
+        }
+        let Node { left, right, .. } = self.arena[index];
+        let removed = match self.arena[index].value.cmp(&value) {
+            Ordering::Less => {
+                let (right, removed) = self.remove_from(right, value);
+                self.arena[index].right = right;
+                removed
+            }
+            Ordering::Equal if left == NIL || right == NIL => {
+                let child = if left == NIL { right } else { left };
+                return (child, Some(self.arena.release(index)));
+            }
+            Ordering::Equal => {
+                let (right, leftmost) = self.remove_leftmost(right);
+                self.arena[index].right = right;
+                Some(replace(&mut self.arena[index].value, leftmost))
+            }
+            Ordering::Greater => {
+                let (left, removed) = self.remove_from(left, value);
+                self.arena[index].left = left;
+                removed
+            }
+        };
+        match removed {
+            Some(_) => (self.rebalance(index), removed),
+            None => (index, None),
+        }
+    }
+
+    fn remove_leftmost(&mut self, index: u32) -> (u32, i64) {
+        let Node { left, right, .. } = self.arena[index];
+        if left == NIL {
+            return (right, self.arena.release(index));
+        }
+        let (left, value) = self.remove_leftmost(left);
+        self.arena[index].left = left;
+        (self.rebalance(index), value)
+    }
+}
+
+impl Tree for ArenaAvlSet {
+    fn insert(&mut self, value: i64) -> bool {
+        let (root, inserted) = self.insert_into(self.root, value);
+        self.root = root;
+        inserted
+    }
+
+    fn remove(&mut self, value: i64) -> Option<i64> {
+        let (root, removed) = self.remove_from(self.root, value);
+        self.root = root;
+        removed
+    }
+
+    fn search(&self, value: i64) -> bool {
+        search(&self.arena, self.root, value)
+    }
+
+    fn values(&self) -> Vec<i64> {
+        self.iter().copied().collect()
+    }
+
+    fn shape(&self) -> Option<Shape> {
+        shape(&self.arena, self.root, &|node| node.value.to_string())
+    }
+}
+
+/// Treap with nodes in an arena instead of separate boxes.
+#[derive(Debug, Clone)]
+pub struct ArenaTreapSet {
+    arena: Arena,
+    root: u32,
+}
+
+impl Default for ArenaTreapSet {
+    fn default() -> Self {
+        Self::with_capacity(0)
+    }
+}
+
+impl ArenaTreapSet {
+    /// Room for `capacity` values before the arena has to grow.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            arena: Arena::with_capacity(capacity),
+            root: NIL,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.arena.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root == NIL
+    }
+
+    pub fn iter(&self) -> ArenaIter<'_> {
+        ArenaIter {
+            arena: &self.arena,
+            prev_nodes: vec![],
+            current: self.root,
+        }
+    }
+
+    fn merge(&mut self, left: u32, right: u32) -> u32 {
+        if left == NIL {
+            return right;
+        }
+        if right == NIL {
+            return left;
+        }
+        if self.arena[left].rank > self.arena[right].rank {
+            let merged = self.merge(self.arena[left].right, right);
+            self.arena[left].right = merged;
+            left
+        } else {
+            let merged = self.merge(left, self.arena[right].left);
+            self.arena[right].left = merged;
+            right
+        }
+    }
+
+    // Values up to `value` go left, the rest go right.
+    fn split(&mut self, index: u32, value: i64) -> (u32, u32) {
+        if index == NIL {
+            return (NIL, NIL);
+        }
+        if self.arena[index].value <= value {
+            let (left, right) = self.split(self.arena[index].right, value);
+            self.arena[index].right = left;
+            (index, right)
+        } else {
+            let (left, right) = self.split(self.arena[index].left, value);
+            self.arena[index].left = right;
+            (left, index)
+        }
+    }
+
+    fn remove_from(&mut self, index: u32, value: i64) -> u32 {
+        let Node { left, right, .. } = self.arena[index];
+        match self.arena[index].value.cmp(&value) {
+            Ordering::Less => self.arena[index].right = self.remove_from(right, value),
+            Ordering::Equal => {
+                self.arena.release(index);
+                return self.merge(left, right);
+            }
+            Ordering::Greater => self.arena[index].left = self.remove_from(left, value),
+        }
+        index
+    }
+}
+
+impl Tree for ArenaTreapSet {
+    fn insert(&mut self, value: i64) -> bool {
+        if self.search(value) {
+            return false;
+        }
+        let node = self.arena.alloc(Node::new(value, rand::thread_rng().gen()));
+        let (left, right) = self.split(self.root, value);
+        let left = self.merge(left, node);
+        self.root = self.merge(left, right);
+        true
+    }
+
+    fn remove(&mut self, value: i64) -> Option<i64> {
+        if !self.search(value) {
+            return None;
+        }
+        self.root = self.remove_from(self.root, value);
+        Some(value)
+    }
+
+    fn search(&self, value: i64) -> bool {
+        search(&self.arena, self.root, value)
+    }
+
+    fn values(&self) -> Vec<i64> {
+        self.iter().copied().collect()
+    }
+
+    fn shape(&self) -> Option<Shape> {
+        // Stretch `u32` priorities over `usize` to scale them like other treaps.
+        let scale = usize::MAX / u32::MAX as usize;
+        shape(&self.arena, self.root, &|node| {
+            priority_label(node.value.to_string(), node.rank as usize * scale)
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct ArenaIter<'a> {
+    arena: &'a Arena,
+    prev_nodes: Vec<u32>,
+    current: u32,
+}
+
+impl<'a> Iterator for ArenaIter<'a> {
+    type Item = &'a i64;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.current != NIL {
+            self.prev_nodes.push(self.current);
+            self.current = self.arena[self.current].left;
+        }
+        let node = &self.arena[self.prev_nodes.pop()?];
+        self.current = node.right;
+        Some(&node.value)
+    }
+}
+
+impl HeapSize for Arena {
+    fn heap_size_bytes(&self) -> usize {
+        self.nodes.capacity() * size_of::<Node>() + self.free.capacity() * size_of::<u32>()
+    }
+}
+
+impl HeapSize for ArenaAvlSet {
+    fn heap_size_bytes(&self) -> usize {
+        self.arena.heap_size_bytes()
+    }
+}
+
+impl HeapSize for ArenaTreapSet {
+    fn heap_size_bytes(&self) -> usize {
+        self.arena.heap_size_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+
+    fn play_against_btree(tree: &mut dyn Tree) -> BTreeSet<i64> {
+        let mut expected = BTreeSet::new();
+        let mut rng = rand::thread_rng();
+        for _ in 0..20000 {
+            let value = rng.gen_range(0..2000);
+            match rng.gen_range(0..3) {
+                0 => assert_eq!(expected.insert(value), tree.insert(value)),
+                1 => assert_eq!(expected.take(&value), tree.remove(value)),
+                _ => assert_eq!(expected.contains(&value), tree.search(value)),
+            }
+        }
+        expected
+    }
+
+    #[test]
+    fn test_avl() {
+        fn balanced(tree: &ArenaAvlSet, index: u32) -> bool {
+            index == NIL
+                || (tree.balance_factor(index).abs() <= 1
+                    && balanced(tree, tree.arena[index].left)
+                    && balanced(tree, tree.arena[index].right))
+        }
+
+        let mut tree = ArenaAvlSet::with_capacity(16);
+        let expected = play_against_btree(&mut tree);
+        assert!(expected.iter().eq(tree.iter()));
+        assert_eq!(expected.len(), tree.len());
+        assert!(balanced(&tree, tree.root));
+        assert!(tree.arena.nodes.len() <= 2000);
+    }
+
+    #[test]
+    fn test_treap() {
+        let mut treap = ArenaTreapSet::default();
+        let expected = play_against_btree(&mut treap);
+        assert!(expected.iter().eq(treap.iter()));
+        assert_eq!(expected.len(), treap.len());
+        assert!(treap.arena.nodes.len() <= 2000);
+        for value in expected {
+            assert_eq!(Some(value), treap.remove(value));
+        }
+        assert!(treap.is_empty());
+        assert_eq!(0, treap.len());
+    }
+}
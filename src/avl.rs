@@ -1,50 +1,139 @@
 use std::{
     cmp::{max, Ordering},
+    fmt::{self, Debug, Formatter},
     mem::{replace, swap},
+    ops::{Bound, RangeBounds},
+    vec,
 };
 
-use crate::Tree;
+use crate::{
+    comparator::Comparator,
+    cursor::{self, BinaryNode, Cursor, CursorMut, CursorSet},
+    link::{Link, Owned},
+    memory::HeapSize,
+    monoid::{after_start, before_end, Monoid},
+    sorted_set::{build_balanced, ordered_set_impls},
+    viz::Shape,
+    Tree,
+};
+
+pub(crate) type AvlTree<M, K = i64, F = Owned> = Option<<F as Link>::Ptr<AvlNode<M, K, F>>>;
+
+pub(crate) struct AvlNode<M: Monoid<K>, K = i64, F: Link = Owned> {
+    pub(crate) value: K,
+    pub(crate) height: usize,
+    pub(crate) summary: M::Summary,
+    pub(crate) left: AvlTree<M, K, F>,
+    pub(crate) right: AvlTree<M, K, F>,
+}
+
+impl<M: Monoid<K>, K: Copy, F: Link> Clone for AvlNode<M, K, F> {
+    fn clone(&self) -> Self {
+        Self {
+            value: self.value,
+            height: self.height,
+            summary: self.summary.clone(),
+            left: self.left.as_ref().map(F::clone_ptr),
+            right: self.right.as_ref().map(F::clone_ptr),
+        }
+    }
+}
 
-type AvlTree = Option<Box<AvlNode>>;
+impl<M: Monoid<K>, K: Debug, F: Link> Debug for AvlNode<M, K, F> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AvlNode")
+            .field("value", &self.value)
+            .field("height", &self.height)
+            .field("summary", &self.summary)
+            .field("left", &self.left.as_deref())
+            .field("right", &self.right.as_deref())
+            .finish()
+    }
+}
 
-#[derive(Debug, Clone, PartialEq)]
-struct AvlNode {
-    value: i64,
-    height: usize,
-    left: AvlTree,
-    right: AvlTree,
+impl<M: Monoid<K>, K: PartialEq, F: Link> PartialEq for AvlNode<M, K, F> {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+            && self.height == other.height
+            && self.summary == other.summary
+            && self.left.as_deref() == other.left.as_deref()
+            && self.right.as_deref() == other.right.as_deref()
+    }
 }
 
-#[derive(Debug, Clone, PartialEq, Default)]
-pub struct AvlSet {
-    root: AvlTree,
+/// AVL tree that keeps a `M` summary of every subtree.
+#[derive(Debug, Clone)]
+pub struct AugmentedAvlSet<M: Monoid> {
+    root: AvlTree<M>,
+    cmp: Comparator,
 }
 
-impl AvlNode {
+pub type AvlSet = AugmentedAvlSet<()>;
+
+impl<M: Monoid> Default for AugmentedAvlSet<M> {
+    fn default() -> Self {
+        Self {
+            root: None,
+            cmp: Comparator::default(),
+        }
+    }
+}
+
+impl<M: Monoid> AugmentedAvlSet<M> {
+    pub fn with_comparator(
+        compare: impl Fn(&i64, &i64) -> Ordering + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            root: None,
+            cmp: Comparator::new(compare),
+        }
+    }
+}
+
+impl<M: Monoid> AvlNode<M> {
     pub fn boxed(value: i64) -> Box<Self> {
-        Box::new(Self {
+        Box::new(Self::new(value))
+    }
+
+    fn join(value: i64, left: AvlTree<M>, right: AvlTree<M>) -> Box<Self> {
+        let mut node = Self::boxed(value);
+        node.left = left;
+        node.right = right;
+        node.update_height();
+        node
+    }
+}
+
+impl<M: Monoid<K>, K: Copy, F: Link> AvlNode<M, K, F> {
+    pub fn new(value: K) -> Self {
+        Self {
             value,
             left: None,
             right: None,
-            height: 0,
-        })
+            height: 1,
+            summary: M::lift(value),
+        }
     }
 
     pub fn update_height(&mut self) {
         self.height = 1 + max(self.left_height(), self.right_height());
+        self.summary = M::combine(
+            &M::combine(&summary::<M, K, F>(&self.left), &M::lift(self.value)),
+            &summary::<M, K, F>(&self.right),
+        );
     }
 
     pub fn rebalance(&mut self) {
         match self.balance_factor() {
             -2 => {
-                let right_node = self.right.as_mut().unwrap();
+                let right_node = F::make_mut(self.right.as_mut().unwrap());
                 if right_node.balance_factor() == 1 {
                     right_node.rotate_right();
                 }
                 self.rotate_left()
             }
             2 => {
-                let left_node = self.left.as_mut().unwrap();
+                let left_node = F::make_mut(self.left.as_mut().unwrap());
                 if left_node.balance_factor() == -1 {
                     left_node.rotate_left()
                 }
@@ -68,58 +157,59 @@ impl AvlNode {
 
     fn rotate_left(&mut self) {
         if self.right.is_some() {
-            let right_node = self.right.as_mut().unwrap();
+            let right_node = F::make_mut(self.right.as_mut().unwrap());
             let right_left_tree = right_node.left.take();
             let right_right_tree = right_node.right.take();
 
             let mut new_left_tree = replace(&mut self.right, right_right_tree);
-            swap(&mut self.value, &mut new_left_tree.as_mut().unwrap().value);
-            let left_tree = self.left.take();
-
-            let new_left_node = new_left_tree.as_mut().unwrap();
+            let new_left_node = F::make_mut(new_left_tree.as_mut().unwrap());
+            swap(&mut self.value, &mut new_left_node.value);
             new_left_node.right = right_left_tree;
-            new_left_node.left = left_tree;
+            new_left_node.left = self.left.take();
+            new_left_node.update_height();
             self.left = new_left_tree;
 
-            if let Some(node) = self.left.as_mut() {
-                node.update_height();
-            }
-
             self.update_height();
         }
     }
 
     fn rotate_right(&mut self) {
         if self.left.is_some() {
-            let left_node = self.left.as_mut().unwrap();
+            let left_node = F::make_mut(self.left.as_mut().unwrap());
             let left_right_tree = left_node.right.take();
             let left_left_tree = left_node.left.take();
 
             let mut new_right_tree = replace(&mut self.left, left_left_tree);
-            swap(&mut self.value, &mut new_right_tree.as_mut().unwrap().value);
-            let right_tree = self.right.take();
-
-            let new_right_node = new_right_tree.as_mut().unwrap();
+            let new_right_node = F::make_mut(new_right_tree.as_mut().unwrap());
+            swap(&mut self.value, &mut new_right_node.value);
             new_right_node.left = left_right_tree;
-            new_right_node.right = right_tree;
+            new_right_node.right = self.right.take();
+            new_right_node.update_height();
             self.right = new_right_tree;
 
-            if let Some(node) = self.right.as_mut() {
-                node.update_height();
-            }
-
             self.update_height();
         }
     }
 }
 
-impl Tree for AvlSet {
+pub(crate) fn shape<M: Monoid, F: Link>(tree: &AvlTree<M, i64, F>) -> Option<Shape> {
+    tree.as_ref().map(|node| {
+        Shape::new(
+            node.value,
+            node.value.to_string(),
+            shape::<M, F>(&node.left),
+            shape::<M, F>(&node.right),
+        )
+    })
+}
+
+impl<M: Monoid> Tree for AugmentedAvlSet<M> {
     fn insert(&mut self, value: i64) -> bool {
-        let mut prev_ptrs = Vec::<*mut AvlNode>::new();
+        let mut prev_ptrs = Vec::<*mut AvlNode<M>>::new();
         let mut current = &mut self.root;
         while let Some(node) = current {
             prev_ptrs.push(&mut **node);
-            current = match node.value.cmp(&value) {
+            current = match self.cmp.compare(&node.value, &value) {
                 Ordering::Less => &mut node.right,
                 Ordering::Equal => return false,
                 Ordering::Greater => &mut node.left,
@@ -134,7 +224,7 @@ impl Tree for AvlSet {
     fn search(&self, value: i64) -> bool {
         let mut current = &self.root;
         while let Some(node) = current {
-            current = match node.value.cmp(&value) {
+            current = match self.cmp.compare(&node.value, &value) {
                 Ordering::Less => &node.right,
                 Ordering::Equal => return true,
                 Ordering::Greater => &node.left,
@@ -144,15 +234,16 @@ impl Tree for AvlSet {
     }
 
     fn remove(&mut self, value: i64) -> Option<i64> {
-        fn find_target<'a>(
+        fn find_target<'a, M: Monoid>(
+            cmp: &Comparator,
             value: i64,
-            start: &'a mut AvlTree,
-            prev_ptrs: &mut Vec<*mut AvlNode>,
-        ) -> Option<&'a mut AvlNode> {
+            start: &'a mut AvlTree<M>,
+            prev_ptrs: &mut Vec<*mut AvlNode<M>>,
+        ) -> Option<&'a mut AvlNode<M>> {
             let mut target = None;
             let mut current = start;
             while let Some(node) = current {
-                match node.value.cmp(&value) {
+                match cmp.compare(&node.value, &value) {
                     Ordering::Less => {
                         prev_ptrs.push(&mut **node);
                         current = &mut node.right;
@@ -170,13 +261,13 @@ impl Tree for AvlSet {
             target
         }
 
-        fn has_zero_or_one_child(target: &AvlNode) -> bool {
+        fn has_zero_or_one_child<M: Monoid>(target: &AvlNode<M>) -> bool {
             target.left.is_none() || target.right.is_none()
         }
 
-        fn try_take_value_when_zero_or_one_child(
-            target: &mut AvlNode,
-            prev_ptr: Option<*mut AvlNode>,
+        fn try_take_value_when_zero_or_one_child<M: Monoid>(
+            target: &mut AvlNode<M>,
+            prev_ptr: Option<*mut AvlNode<M>>,
         ) -> Option<i64> {
             let value = if let Some(left_node) = target.left.take() {
                 replace(target, *left_node).value
@@ -198,12 +289,12 @@ impl Tree for AvlSet {
             Some(value)
         }
 
-        fn take_value_when_two_children(target: &mut AvlNode) -> i64 {
-            fn tree_has_no_left_children(tree: &AvlTree) -> bool {
+        fn take_value_when_two_children<M: Monoid>(target: &mut AvlNode<M>) -> i64 {
+            fn tree_has_no_left_children<M: Monoid>(tree: &AvlTree<M>) -> bool {
                 tree.as_ref().unwrap().left.is_none()
             }
 
-            fn raise_right_tree(target: &mut AvlNode) -> i64 {
+            fn raise_right_tree<M: Monoid>(target: &mut AvlNode<M>) -> i64 {
                 let right_node = &mut target.right.take().unwrap();
                 let value = replace(&mut target.value, right_node.value);
                 target.right = right_node.right.take();
@@ -212,9 +303,9 @@ impl Tree for AvlSet {
                 value
             }
 
-            fn swap_with_leftmost_node_from_right_tree(target: &mut AvlNode) -> i64 {
-                fn traverse_to_leftmost(tree: &mut AvlTree) -> Vec<*mut AvlNode> {
-                    let mut inner_ptrs = Vec::<*mut AvlNode>::new();
+            fn swap_with_leftmost_node_from_right_tree<M: Monoid>(target: &mut AvlNode<M>) -> i64 {
+                fn traverse_to_leftmost<M: Monoid>(tree: &mut AvlTree<M>) -> Vec<*mut AvlNode<M>> {
+                    let mut inner_ptrs = Vec::<*mut AvlNode<M>>::new();
                     let mut current = tree;
                     while let Some(node) = current {
                         if node.left.is_some() {
@@ -225,7 +316,10 @@ impl Tree for AvlSet {
                     inner_ptrs
                 }
 
-                fn safely_replace_value(value: &mut i64, leftmost_parent: &mut AvlNode) -> i64 {
+                fn safely_replace_value<M: Monoid>(
+                    value: &mut i64,
+                    leftmost_parent: &mut AvlNode<M>,
+                ) -> i64 {
                     let mut leftmost_node = leftmost_parent.left.take().unwrap();
                     let value = replace(value, leftmost_node.value);
                     leftmost_parent.left = leftmost_node.right.take();
@@ -250,8 +344,8 @@ impl Tree for AvlSet {
             }
         }
 
-        let mut prev_ptrs = Vec::<*mut AvlNode>::new();
-        let target = find_target(value, &mut self.root, &mut prev_ptrs)?;
+        let mut prev_ptrs = Vec::<*mut AvlNode<M>>::new();
+        let target = find_target(&self.cmp, value, &mut self.root, &mut prev_ptrs)?;
         let taken_value = if has_zero_or_one_child(target) {
             try_take_value_when_zero_or_one_child(target, prev_ptrs.pop())
                 .unwrap_or_else(|| self.root.take().unwrap().value) // None means to remove root of the tree
@@ -261,17 +355,70 @@ impl Tree for AvlSet {
         update_height_rebalance(prev_ptrs);
         Some(taken_value)
     }
+
+    fn values(&self) -> Vec<i64> {
+        self.iter().copied().collect()
+    }
+
+    fn shape(&self) -> Option<Shape> {
+        shape::<M, Owned>(&self.root)
+    }
 }
 
-impl<'a> AvlSet {
-    pub fn iter(&'a self) -> AvlIter<'a> {
+impl<'a, M: Monoid> AugmentedAvlSet<M> {
+    pub fn iter(&'a self) -> AvlIter<'a, M> {
         AvlIter {
             prev_nodes: Vec::new(),
             current: &self.root,
         }
     }
+
+    /// Summary of the whole tree.
+    pub fn summary(&self) -> M::Summary {
+        summary::<M, i64, Owned>(&self.root)
+    }
+
+    /// Summary of the keys inside `range` in O(log n).
+    pub fn fold(&self, range: impl RangeBounds<i64>) -> M::Summary {
+        fold(&self.cmp, &self.root, &range)
+    }
 }
-fn update_height_rebalance(prev_ptrs: Vec<*mut AvlNode>) {
+
+fn summary<M: Monoid<K>, K, F: Link>(tree: &AvlTree<M, K, F>) -> M::Summary {
+    tree.as_ref()
+        .map_or_else(M::identity, |node| node.summary.clone())
+}
+
+// Descends while the whole range lies on one side of a node. Once it splits,
+// each side has only one open bound and costs one more root-to-leaf path.
+fn fold<M: Monoid>(
+    cmp: &Comparator,
+    tree: &AvlTree<M>,
+    range: &impl RangeBounds<i64>,
+) -> M::Summary {
+    let node = match tree {
+        None => return M::identity(),
+        Some(node) => node,
+    };
+    let (start, end) = (range.start_bound(), range.end_bound());
+    if !after_start(cmp, start, node.value) {
+        return fold(cmp, &node.right, range);
+    }
+    if !before_end(cmp, end, node.value) {
+        return fold(cmp, &node.left, range);
+    }
+    let left = match start {
+        Bound::Unbounded => summary::<M, i64, Owned>(&node.left),
+        _ => fold(cmp, &node.left, &(start, Bound::Unbounded)),
+    };
+    let right = match end {
+        Bound::Unbounded => summary::<M, i64, Owned>(&node.right),
+        _ => fold(cmp, &node.right, &(Bound::Unbounded, end)),
+    };
+    M::combine(&M::combine(&left, &M::lift(node.value)), &right)
+}
+
+fn update_height_rebalance<M: Monoid>(prev_ptrs: Vec<*mut AvlNode<M>>) {
     for ptr in prev_ptrs.into_iter().rev() {
         let node = unsafe { &mut *ptr };
         node.update_height();
@@ -279,13 +426,109 @@ fn update_height_rebalance(prev_ptrs: Vec<*mut AvlNode>) {
     }
 }
 
+// Recursive counterparts of `Tree::insert` and `Tree::remove` for trees whose
+// nodes may be shared: every node on the path is reached through
+// `Link::make_mut`, so a shared node is copied before it changes.
+pub(crate) fn insert_by<M: Monoid<K>, K: Copy, F: Link>(
+    tree: &mut AvlTree<M, K, F>,
+    value: K,
+    compare: &impl Fn(&K, &K) -> Ordering,
+) -> bool {
+    let node = match tree {
+        None => {
+            *tree = Some(F::new(AvlNode::new(value)));
+            return true;
+        }
+        Some(node) => F::make_mut(node),
+    };
+    let inserted = match compare(&node.value, &value) {
+        Ordering::Less => insert_by::<M, K, F>(&mut node.right, value, compare),
+        Ordering::Equal => false,
+        Ordering::Greater => insert_by::<M, K, F>(&mut node.left, value, compare),
+    };
+    if inserted {
+        node.update_height();
+        node.rebalance();
+    }
+    inserted
+}
+
+fn remove_leftmost<M: Monoid<K>, K: Copy, F: Link>(tree: &mut AvlTree<M, K, F>) -> Option<K> {
+    let node = F::make_mut(tree.as_mut()?);
+    if node.left.is_none() {
+        let value = node.value;
+        let right = node.right.take();
+        *tree = right;
+        return Some(value);
+    }
+    let leftmost = remove_leftmost::<M, K, F>(&mut node.left);
+    node.update_height();
+    node.rebalance();
+    leftmost
+}
+
+pub(crate) fn remove_by<M: Monoid<K>, K: Copy, F: Link>(
+    tree: &mut AvlTree<M, K, F>,
+    value: K,
+    compare: &impl Fn(&K, &K) -> Ordering,
+) -> Option<K> {
+    let node = F::make_mut(tree.as_mut()?);
+    let removed = match compare(&node.value, &value) {
+        Ordering::Less => remove_by::<M, K, F>(&mut node.right, value, compare),
+        Ordering::Equal => {
+            let removed = node.value;
+            match remove_leftmost::<M, K, F>(&mut node.right) {
+                Some(next) => node.value = next,
+                None => {
+                    let left = node.left.take();
+                    *tree = left;
+                    return Some(removed);
+                }
+            }
+            Some(removed)
+        }
+        Ordering::Greater => remove_by::<M, K, F>(&mut node.left, value, compare),
+    };
+    if removed.is_some() {
+        node.update_height();
+        node.rebalance();
+    }
+    removed
+}
+
+// Lets `f` change the stored value equal to `key` in place, for keys that
+// carry more than what `compare` looks at. Returns `false` if there is none.
+pub(crate) fn update_by<M: Monoid<K>, K: Copy, F: Link>(
+    tree: &mut AvlTree<M, K, F>,
+    key: &K,
+    compare: &impl Fn(&K, &K) -> Ordering,
+    f: impl FnOnce(&mut K),
+) -> bool {
+    let Some(node) = tree.as_mut() else {
+        return false;
+    };
+    let node = F::make_mut(node);
+    let found = match compare(&node.value, key) {
+        Ordering::Less => update_by::<M, K, F>(&mut node.right, key, compare, f),
+        Ordering::Equal => {
+            f(&mut node.value);
+            true
+        }
+        Ordering::Greater => update_by::<M, K, F>(&mut node.left, key, compare, f),
+    };
+    if found {
+        node.update_height();
+    }
+    found
+}
+
 #[derive(Debug)]
-pub struct AvlIter<'a> {
-    prev_nodes: Vec<&'a AvlNode>,
-    current: &'a AvlTree,
+pub struct AvlIter<'a, M: Monoid> {
+    prev_nodes: Vec<&'a AvlNode<M>>,
+    current: &'a AvlTree<M>,
 }
 
-impl<'a> Iterator for AvlIter<'a> {
+impl<'a, M: Monoid> Iterator for AvlIter<'a, M> {
     type Item = &'a i64;
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -316,13 +559,559 @@ impl<'a> Iterator for AvlIter<'a> {
     }
 }
 
+impl<M: Monoid> AugmentedAvlSet<M> {
+    pub fn drain(&mut self) -> AvlIntoIter<M> {
+        AvlIntoIter {
+            prev_nodes: vec![],
+            current: self.root.take(),
+        }
+    }
+
+    /// Keeps the values matching `f`. The tree is rebuilt in O(n).
+    pub fn retain(&mut self, mut f: impl FnMut(&i64) -> bool) {
+        let kept = self.drain().filter(|value| f(value)).collect::<Vec<i64>>();
+        self.root = build_balanced(&kept, &AvlNode::join);
+    }
+
+    /// Removes the values matching `pred` right away and returns them in order.
+    /// The tree is rebuilt in O(n).
+    pub fn extract_if(&mut self, mut pred: impl FnMut(&i64) -> bool) -> vec::IntoIter<i64> {
+        let (extracted, kept): (Vec<i64>, Vec<i64>) = self.drain().partition(|value| pred(value));
+        self.root = build_balanced(&kept, &AvlNode::join);
+        extracted.into_iter()
+    }
+
+    fn with_sorted(&self, sorted: &[i64]) -> Self {
+        Self {
+            root: build_balanced(sorted, &AvlNode::join),
+            cmp: self.cmp.clone(),
+        }
+    }
+}
+
+#[cfg(feature = "rayon")]
+mod par {
+    use rayon::iter::{FromParallelIterator, IntoParallelIterator, ParallelIterator};
+
+    use crate::parallel::{self, sorted_unique};
+
+    use super::*;
+
+    fn height<M: Monoid>(tree: &AvlTree<M>) -> usize {
+        tree.as_ref().map_or(0, |node| node.height)
+    }
+
+    // Hangs `left` and `right` under `node`. When their heights differ by more
+    // than one, `node` goes down the spine of the taller tree and every node on
+    // the way back up is rebalanced, as after an insert.
+    fn join<M: Monoid>(
+        left: AvlTree<M>,
+        mut node: Box<AvlNode<M>>,
+        right: AvlTree<M>,
+    ) -> Box<AvlNode<M>> {
+        let (left_height, right_height) = (height(&left), height(&right));
+        if left_height > right_height + 1 {
+            let mut root = left.unwrap();
+            root.right = Some(join(root.right.take(), node, right));
+            root.update_height();
+            root.rebalance();
+            root
+        } else if right_height > left_height + 1 {
+            let mut root = right.unwrap();
+            root.left = Some(join(left, node, root.left.take()));
+            root.update_height();
+            root.rebalance();
+            root
+        } else {
+            node.left = left;
+            node.right = right;
+            node.update_height();
+            node
+        }
+    }
+
+    fn join_two<M: Monoid>(left: AvlTree<M>, right: AvlTree<M>) -> AvlTree<M> {
+        fn split_last<M: Monoid>(mut node: Box<AvlNode<M>>) -> (AvlTree<M>, Box<AvlNode<M>>) {
+            match node.right.take() {
+                None => (node.left.take(), node),
+                Some(right) => {
+                    let (rest, last) = split_last(right);
+                    let left = node.left.take();
+                    (Some(join(left, node, rest)), last)
+                }
+            }
+        }
+
+        match left {
+            None => right,
+            Some(left) => {
+                let (rest, last) = split_last(left);
+                Some(join(rest, last, right))
+            }
+        }
+    }
+
+    // Splits into the keys before and after `value`; the node equal to it is
+    // dropped and reported.
+    fn split_out<M: Monoid>(
+        cmp: &Comparator,
+        tree: AvlTree<M>,
+        value: i64,
+    ) -> (AvlTree<M>, bool, AvlTree<M>) {
+        let mut node = match tree {
+            Some(node) => node,
+            None => return (None, false, None),
+        };
+        let (left, right) = (node.left.take(), node.right.take());
+        match cmp.compare(&node.value, &value) {
+            Ordering::Less => {
+                let (less, found, greater) = split_out(cmp, right, value);
+                (Some(join(left, node, less)), found, greater)
+            }
+            Ordering::Equal => (left, true, right),
+            Ordering::Greater => {
+                let (less, found, greater) = split_out(cmp, left, value);
+                (less, found, Some(join(greater, node, right)))
+            }
+        }
+    }
+
+    fn union<M: Monoid>(
+        cmp: &Comparator,
+        depth: usize,
+        t1: AvlTree<M>,
+        t2: AvlTree<M>,
+    ) -> AvlTree<M>
+    where
+        M::Summary: Send,
+    {
+        let (mut node, other) = match (t1, t2) {
+            (None, t) | (t, None) => return t,
+            (Some(node), other) => (node, other),
+        };
+        let (less, _, greater) = split_out(cmp, other, node.value);
+        let (node_left, node_right) = (node.left.take(), node.right.take());
+        let (left, right) = parallel::join(
+            depth,
+            || union(cmp, depth + 1, node_left, less),
+            || union(cmp, depth + 1, node_right, greater),
+        );
+        Some(join(left, node, right))
+    }
+
+    fn intersection<M: Monoid>(
+        cmp: &Comparator,
+        depth: usize,
+        t1: AvlTree<M>,
+        t2: AvlTree<M>,
+    ) -> AvlTree<M>
+    where
+        M::Summary: Send,
+    {
+        let (mut node, other) = match (t1, t2) {
+            (Some(node), other @ Some(_)) => (node, other),
+            _ => return None,
+        };
+        let (less, found, greater) = split_out(cmp, other, node.value);
+        let (node_left, node_right) = (node.left.take(), node.right.take());
+        let (left, right) = parallel::join(
+            depth,
+            || intersection(cmp, depth + 1, node_left, less),
+            || intersection(cmp, depth + 1, node_right, greater),
+        );
+        if found {
+            Some(join(left, node, right))
+        } else {
+            join_two(left, right)
+        }
+    }
+
+    fn difference<M: Monoid>(
+        cmp: &Comparator,
+        depth: usize,
+        t1: AvlTree<M>,
+        t2: AvlTree<M>,
+    ) -> AvlTree<M>
+    where
+        M::Summary: Send,
+    {
+        let (mut node, other) = match (t1, t2) {
+            (None, _) => return None,
+            (t, None) => return t,
+            (Some(node), other) => (node, other),
+        };
+        let (less, found, greater) = split_out(cmp, other, node.value);
+        let (node_left, node_right) = (node.left.take(), node.right.take());
+        let (left, right) = parallel::join(
+            depth,
+            || difference(cmp, depth + 1, node_left, less),
+            || difference(cmp, depth + 1, node_right, greater),
+        );
+        if found {
+            join_two(left, right)
+        } else {
+            Some(join(left, node, right))
+        }
+    }
+
+    /// Bulk operations that split the work between the threads of the rayon
+    /// pool. The result keeps the comparator of `self`. Splitting needs both
+    /// sets to order keys the same way, so if `other` uses another comparator
+    /// they fall back to the serial operators, which re-sort it.
+    impl<M: Monoid> AugmentedAvlSet<M>
+    where
+        M::Summary: Send,
+    {
+        pub fn par_union(mut self, other: Self) -> Self {
+            if self.cmp != other.cmp {
+                return &self | &other;
+            }
+            self.root = union(&self.cmp, 0, self.root.take(), other.root);
+            self
+        }
+
+        pub fn par_intersection(mut self, other: Self) -> Self {
+            if self.cmp != other.cmp {
+                return &self & &other;
+            }
+            self.root = intersection(&self.cmp, 0, self.root.take(), other.root);
+            self
+        }
+
+        pub fn par_difference(mut self, other: Self) -> Self {
+            if self.cmp != other.cmp {
+                return &self - &other;
+            }
+            self.root = difference(&self.cmp, 0, self.root.take(), other.root);
+            self
+        }
+    }
+
+    /// Sorts the values in parallel and builds a perfectly balanced tree in O(n).
+    impl<M: Monoid> FromParallelIterator<i64> for AugmentedAvlSet<M>
+    where
+        M::Summary: Send,
+    {
+        fn from_par_iter<I: IntoParallelIterator<Item = i64>>(values: I) -> Self {
+            let mut set = Self::default();
+            let sorted = sorted_unique(&set.cmp, values.into_par_iter().collect());
+            set.root = parallel::build_balanced(0, &sorted, &AvlNode::join);
+            set
+        }
+    }
+}
+
+impl<M: Monoid> AugmentedAvlSet<M> {
+    /// Smallest value, in the order of the comparator.
+    pub fn first(&self) -> Option<&i64> {
+        cursor::first(self.root())
+    }
+
+    /// Largest value, in the order of the comparator.
+    pub fn last(&self) -> Option<&i64> {
+        cursor::last(self.root())
+    }
+
+    /// Cursor at the ghost position between the last and the first value.
+    pub fn cursor(&self) -> Cursor<'_> {
+        Cursor::new(self)
+    }
+
+    pub fn cursor_mut(&mut self) -> CursorMut<'_> {
+        CursorMut::new(self)
+    }
+}
+
+impl<M: Monoid> BinaryNode for AvlNode<M> {
+    fn value(&self) -> &i64 {
+        &self.value
+    }
+
+    fn left(&self) -> Option<&dyn BinaryNode> {
+        self.left.as_deref().map(|node| node as &dyn BinaryNode)
+    }
+
+    fn right(&self) -> Option<&dyn BinaryNode> {
+        self.right.as_deref().map(|node| node as &dyn BinaryNode)
+    }
+}
+
+impl<M: Monoid> CursorSet for AugmentedAvlSet<M> {
+    fn root(&self) -> Option<&dyn BinaryNode> {
+        self.root.as_deref().map(|node| node as &dyn BinaryNode)
+    }
+
+    fn comparator(&self) -> &Comparator {
+        &self.cmp
+    }
+}
+
+ordered_set_impls!(AugmentedAvlSet<M: Monoid>, AvlIter);
+
+impl<M: Monoid> IntoIterator for AugmentedAvlSet<M> {
+    type Item = i64;
+    type IntoIter = AvlIntoIter<M>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        AvlIntoIter {
+            prev_nodes: vec![],
+            current: self.root,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct AvlIntoIter<M: Monoid> {
+    prev_nodes: Vec<AvlNode<M>>,
+    current: AvlTree<M>,
+}
+
+impl<M: Monoid> Iterator for AvlIntoIter<M> {
+    type Item = i64;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(mut node) = self.current.take() {
+            self.current = node.left.take();
+            self.prev_nodes.push(*node);
+        }
+        let mut node = self.prev_nodes.pop()?;
+        self.current = node.right.take();
+        Some(node.value)
+    }
+}
+
+impl<M: Monoid> HeapSize for AugmentedAvlSet<M> {
+    fn heap_size_bytes(&self) -> usize {
+        self.iter().count() * size_of::<AvlNode<M>>() + self.cmp.heap_size_bytes()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::{path::Path, time::Instant};
 
-    use crate::tester::run_test;
+    use crate::{
+        monoid::{Count, Max, Sum},
+        multiset::AvlMultiSet,
+        tester::run_test,
+    };
 
     use super::*;
+    use crate::cursor::UnorderedKeyError;
+
+    #[test]
+    fn test_fold() {
+        let mut sums = AugmentedAvlSet::<Sum>::default();
+        let mut counts = AugmentedAvlSet::<Count>::default();
+        for value in (0..100).rev() {
+            sums.insert(value);
+            counts.insert(value);
+        }
+        for value in [0, 10, 42, 55, 99] {
+            sums.remove(value);
+            counts.remove(value);
+        }
+        let expected = |lo: i64, hi: i64| {
+            (lo..hi)
+                .filter(|x| ![0, 10, 42, 55, 99].contains(x))
+                .sum::<i64>()
+        };
+        assert_eq!(expected(0, 100), sums.summary());
+        assert_eq!(expected(5, 60), sums.fold(5..60));
+        assert_eq!(expected(42, 43), sums.fold(42..=42));
+        assert_eq!(expected(43, 100), sums.fold(43..));
+        assert_eq!(40, counts.fold(..42));
+        assert_eq!(None, AugmentedAvlSet::<Max>::default().fold(..));
+    }
+
+    #[test]
+    fn test_comparator() {
+        let mut tree = AugmentedAvlSet::<Sum>::with_comparator(|a, b| b.cmp(a));
+        for value in 0..20 {
+            tree.insert(value);
+        }
+        tree.remove(7);
+        assert!(tree.search(3));
+        assert!(!tree.search(7));
+        assert_eq!(19, *tree.iter().next().unwrap());
+        assert_eq!(
+            10 + 9 + 8 + 6,
+            tree.fold((Bound::Included(10), Bound::Included(6)))
+        );
+        assert_eq!(0, tree.fold(6..=10));
+    }
+
+    #[test]
+    fn test_set_operations() {
+        let evens = (0..20).step_by(2).collect::<AvlSet>();
+        let threes = (0..20).step_by(3).collect::<AvlSet>();
+        let union = &evens | &threes;
+        assert_eq!(
+            vec![0, 2, 3, 4, 6, 8, 9, 10, 12, 14, 15, 16, 18],
+            union.iter().copied().collect::<Vec<i64>>()
+        );
+        assert_eq!(
+            vec![0, 6, 12, 18],
+            (&evens & &threes).into_iter().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![3, 9, 15],
+            (&threes - &evens).into_iter().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            union,
+            (0..20).rev().filter(|x| x % 2 == 0 || x % 3 == 0).collect()
+        );
+        let mut reversed = AvlSet::with_comparator(|a, b| b.cmp(a));
+        reversed.extend(&threes);
+        assert_eq!(reversed, threes);
+        assert_eq!(
+            vec![18, 16, 15, 14, 12, 10, 9, 8, 6, 4, 3, 2, 0],
+            (&reversed | &evens).into_iter().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![2, 4, 8, 10, 14, 16],
+            (&evens - &reversed).into_iter().collect::<Vec<_>>()
+        );
+        let big = (0..1000).collect::<AvlSet>();
+        assert!((&big & &big).root.as_ref().map_or(0, |node| node.height) <= 10);
+    }
+
+    // Height of the tree after checking the stored height and the balance
+    // of every node.
+    fn checked_height<M: Monoid>(tree: &AvlTree<M>) -> usize {
+        let Some(node) = tree else {
+            return 0;
+        };
+        let (left, right) = (checked_height(&node.left), checked_height(&node.right));
+        assert!(left.abs_diff(right) <= 1, "unbalanced at {}", node.value);
+        assert_eq!(1 + left.max(right), node.height);
+        node.height
+    }
+
+    #[test]
+    fn test_retain_extract() {
+        let mut tree = (0..1000).collect::<AugmentedAvlSet<Sum>>();
+        tree.retain(|x| x % 3 == 0);
+        assert_eq!(9, checked_height(&tree.root));
+        assert_eq!((0..1000).step_by(3).sum::<i64>(), tree.summary());
+        let extracted = tree.extract_if(|x| *x >= 500).collect::<Vec<i64>>();
+        assert_eq!((501..1000).step_by(3).collect::<Vec<i64>>(), extracted);
+        assert_eq!(8, checked_height(&tree.root));
+        // The rebuilt tree keeps rebalancing on later updates.
+        for value in (1..500).step_by(3) {
+            tree.insert(value);
+        }
+        checked_height(&tree.root);
+        assert_eq!(334, tree.drain().count());
+        assert_eq!(0, tree.summary());
+    }
+
+    #[test]
+    fn test_cursor() {
+        let tree = (0..100).step_by(10).collect::<AvlSet>();
+        let mut cursor = tree.cursor();
+        assert_eq!(None, cursor.peek());
+        cursor.move_prev();
+        assert_eq!(Some(&90), cursor.peek());
+        cursor.seek(35);
+        assert_eq!(Some(&40), cursor.peek());
+        cursor.move_prev();
+        cursor.move_prev();
+        assert_eq!(Some(&20), cursor.peek());
+        let mut seen = vec![];
+        while let Some(value) = cursor.peek() {
+            seen.push(*value);
+            cursor.move_next();
+        }
+        assert_eq!(vec![20, 30, 40, 50, 60, 70, 80, 90], seen);
+        cursor.seek(91);
+        assert_eq!(None, cursor.peek());
+    }
+
+    #[test]
+    fn test_cursor_mut() {
+        let mut tree = (0..10).collect::<AvlSet>();
+        let other = [2, 3, 5, 7].into_iter().collect::<AvlSet>();
+        let mut theirs = other.cursor();
+        theirs.move_next();
+        let mut ours = tree.cursor_mut();
+        ours.move_next();
+        while let (Some(&a), Some(&b)) = (ours.peek(), theirs.peek()) {
+            match a.cmp(&b) {
+                Ordering::Less => ours.move_next(),
+                Ordering::Equal => {
+                    assert_eq!(Some(a), ours.remove_current());
+                    theirs.move_next();
+                }
+                Ordering::Greater => theirs.move_next(),
+            }
+        }
+        assert_eq!(Some(&8), ours.peek());
+        assert_eq!(Err(UnorderedKeyError { key: 9 }), ours.insert_after(9));
+        ours.move_prev();
+        assert_eq!(Some(&6), ours.peek());
+        assert_eq!(Ok(()), ours.insert_after(7));
+        ours.seek(100);
+        assert_eq!(Ok(()), ours.insert_after(-1));
+        assert_eq!(
+            vec![-1, 0, 1, 4, 6, 7, 8, 9],
+            tree.iter().copied().collect::<Vec<i64>>()
+        );
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_parallel_set_operations() {
+        use std::collections::BTreeSet;
+
+        use rand::Rng;
+        use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+        fn balanced<M: Monoid>(tree: &AvlTree<M>) -> bool {
+            tree.as_ref().is_none_or(|node| {
+                node.balance_factor().abs() <= 1
+                    && node.height == 1 + max(node.left_height(), node.right_height())
+                    && balanced(&node.left)
+                    && balanced(&node.right)
+            })
+        }
+
+        let mut rng = rand::thread_rng();
+        let a = (0..50000)
+            .map(|_| rng.gen_range(0..100000))
+            .collect::<Vec<i64>>();
+        let b = (0..20000)
+            .map(|_| rng.gen_range(0..100000))
+            .collect::<Vec<i64>>();
+        let (a_set, b_set) = (
+            a.iter().copied().collect::<BTreeSet<i64>>(),
+            b.iter().copied().collect::<BTreeSet<i64>>(),
+        );
+        let tree = |values: &[i64]| {
+            values
+                .to_vec()
+                .into_par_iter()
+                .collect::<AugmentedAvlSet<Sum>>()
+        };
+        assert!(a_set.iter().eq(tree(&a).iter()));
+        for (result, expected) in [
+            (tree(&a).par_union(tree(&b)), &a_set | &b_set),
+            (tree(&a).par_intersection(tree(&b)), &a_set & &b_set),
+            (tree(&a).par_difference(tree(&b)), &a_set - &b_set),
+            (tree(&b).par_difference(tree(&a)), &b_set - &a_set),
+        ] {
+            assert!(balanced(&result.root));
+            assert_eq!(expected.iter().sum::<i64>(), result.summary());
+            assert!(expected.into_iter().eq(result.into_iter()));
+        }
+        assert!(tree(&[]).par_union(tree(&a)).iter().eq(a_set.iter()));
+        // A reversed `other` cannot be split by the keys of `self`.
+        let mut reversed = AugmentedAvlSet::<Sum>::with_comparator(|a, b| b.cmp(a));
+        reversed.extend(&b_set);
+        let union = tree(&a).par_union(reversed);
+        assert!((&a_set | &b_set).into_iter().eq(union.into_iter()));
+    }
 
     #[test]
     fn avl_sort_random() {
@@ -332,7 +1121,7 @@ mod tests {
 
     fn perform_sort_test(path: impl AsRef<Path>) {
         run_test(path, |data| {
-            let mut tree = AvlSet::default();
+            let mut tree = AvlMultiSet::default();
             let start = Instant::now();
             let mut n = 0;
             for number in data[1].split(' ').filter_map(|x| x.parse::<i64>().ok()) {
@@ -345,6 +1134,6 @@ mod tests {
                 .map(|x| x.to_string())
                 .collect::<Vec<String>>()
                 .join(" ")
-        })
+        });
     }
 }
@@ -0,0 +1,273 @@
+use std::{
+    fs,
+    io::{self, ErrorKind},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::workload::{Operation, WorkloadResult};
+
+/// One phase of one workload on one structure, flat enough for a CSV row.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BenchRecord {
+    pub structure: String,
+    pub n: usize,
+    pub workload: String,
+    pub phase: String,
+    pub operations: usize,
+    pub succeeded: usize,
+    pub time_ns: u64,
+    pub ops_per_sec: f64,
+    pub p50_ns: u64,
+    pub p90_ns: u64,
+    pub p99_ns: u64,
+    pub p999_ns: u64,
+    pub max_ns: u64,
+    pub peak_bytes: Option<usize>,
+    pub retained_bytes: Option<usize>,
+}
+
+const CSV_HEADER: &str = "structure,n,workload,phase,operations,succeeded,time_ns,ops_per_sec,\
+                          p50_ns,p90_ns,p99_ns,p999_ns,max_ns,peak_bytes,retained_bytes";
+
+impl BenchRecord {
+    /// A record per phase of `result`. Memory usage belongs to the whole run
+    /// and is repeated in every record.
+    pub fn from_result(structure: &str, n: usize, result: &WorkloadResult) -> Vec<Self> {
+        result
+            .phases
+            .iter()
+            .map(|phase| {
+                let mut latency = phase.latency(Operation::Search).clone();
+                latency.merge(phase.latency(Operation::Insert));
+                latency.merge(phase.latency(Operation::Remove));
+                let summary = latency.summary();
+                let nanos = |duration: std::time::Duration| duration.as_nanos() as u64;
+                Self {
+                    structure: structure.to_string(),
+                    n,
+                    workload: result.workload.key(),
+                    phase: phase.phase.to_string(),
+                    operations: phase.operations(),
+                    succeeded: Operation::ALL
+                        .map(|operation| phase.count(operation).succeeded)
+                        .iter()
+                        .sum(),
+                    time_ns: nanos(phase.elapsed),
+                    ops_per_sec: phase.ops_per_sec(),
+                    p50_ns: nanos(summary.p50),
+                    p90_ns: nanos(summary.p90),
+                    p99_ns: nanos(summary.p99),
+                    p999_ns: nanos(summary.p999),
+                    max_ns: nanos(summary.max),
+                    peak_bytes: result.memory.map(|usage| usage.peak),
+                    retained_bytes: result.memory.map(|usage| usage.retained),
+                }
+            })
+            .collect()
+    }
+
+    fn key(&self) -> (&str, usize, &str, &str) {
+        (&self.structure, self.n, &self.workload, &self.phase)
+    }
+
+    fn to_csv(&self) -> String {
+        let optional = |value: Option<usize>| value.map(|v| v.to_string()).unwrap_or_default();
+        format!(
+            "{},{},{},{},{},{},{},{:.1},{},{},{},{},{},{},{}",
+            self.structure,
+            self.n,
+            self.workload,
+            self.phase,
+            self.operations,
+            self.succeeded,
+            self.time_ns,
+            self.ops_per_sec,
+            self.p50_ns,
+            self.p90_ns,
+            self.p99_ns,
+            self.p999_ns,
+            self.max_ns,
+            optional(self.peak_bytes),
+            optional(self.retained_bytes),
+        )
+    }
+
+    fn from_csv(line: &str) -> io::Result<Self> {
+        let fields = line.split(',').map(str::trim).collect::<Vec<_>>();
+        if fields.len() != CSV_HEADER.split(',').count() {
+            return Err(invalid(format!("wrong number of fields in {line:?}")));
+        }
+        fn parse<T: std::str::FromStr>(field: &str) -> io::Result<T> {
+            field
+                .parse()
+                .map_err(|_| invalid(format!("cannot parse {field:?}")))
+        }
+        let optional = |field: &str| match field {
+            "" => Ok(None),
+            field => parse(field).map(Some),
+        };
+        Ok(Self {
+            structure: fields[0].to_string(),
+            n: parse(fields[1])?,
+            workload: fields[2].to_string(),
+            phase: fields[3].to_string(),
+            operations: parse(fields[4])?,
+            succeeded: parse(fields[5])?,
+            time_ns: parse(fields[6])?,
+            ops_per_sec: parse(fields[7])?,
+            p50_ns: parse(fields[8])?,
+            p90_ns: parse(fields[9])?,
+            p99_ns: parse(fields[10])?,
+            p999_ns: parse(fields[11])?,
+            max_ns: parse(fields[12])?,
+            peak_bytes: optional(fields[13])?,
+            retained_bytes: optional(fields[14])?,
+        })
+    }
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message)
+}
+
+fn is_csv(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension == "csv")
+}
+
+pub fn to_json(records: &[BenchRecord]) -> String {
+    serde_json::to_string_pretty(records).unwrap()
+}
+
+pub fn to_csv(records: &[BenchRecord]) -> String {
+    let mut csv = String::from(CSV_HEADER);
+    for record in records {
+        csv.push('\n');
+        csv.push_str(&record.to_csv());
+    }
+    csv.push('\n');
+    csv
+}
+
+/// Writes CSV when the file name ends with `.csv` and JSON otherwise.
+pub fn write_records(path: impl AsRef<Path>, records: &[BenchRecord]) -> io::Result<()> {
+    let path = path.as_ref();
+    let content = if is_csv(path) {
+        to_csv(records)
+    } else {
+        to_json(records)
+    };
+    fs::write(path, content)
+}
+
+/// Reads a file written by `write_records`.
+pub fn read_records(path: impl AsRef<Path>) -> io::Result<Vec<BenchRecord>> {
+    let path = path.as_ref();
+    let content = fs::read_to_string(path)?;
+    if is_csv(path) {
+        content
+            .lines()
+            .skip(1)
+            .filter(|line| !line.trim().is_empty())
+            .map(BenchRecord::from_csv)
+            .collect()
+    } else {
+        serde_json::from_str(&content).map_err(|error| invalid(error.to_string()))
+    }
+}
+
+/// Matching records of two runs. Changes are relative: `-0.1` is 10% lower.
+/// A change is `None` when the old run measured zero, so there is nothing to
+/// compare against.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparison {
+    pub structure: String,
+    pub n: usize,
+    pub workload: String,
+    pub phase: String,
+    pub throughput_change: Option<f64>,
+    pub p99_change: Option<f64>,
+    pub regression: bool,
+}
+
+/// Pairs records with the same structure, n, workload and phase. A pair is a
+/// regression when throughput dropped or p99 latency grew by more than
+/// `threshold`, given as a fraction. Records without a pair are skipped, and
+/// a change without a baseline is never a regression.
+pub fn compare(old: &[BenchRecord], new: &[BenchRecord], threshold: f64) -> Vec<Comparison> {
+    new.iter()
+        .filter_map(|new| {
+            let old = old.iter().find(|old| old.key() == new.key())?;
+            let change = |old: f64, new: f64| (old > 0.0).then(|| new / old - 1.0);
+            let throughput_change = change(old.ops_per_sec, new.ops_per_sec);
+            let p99_change = change(old.p99_ns as f64, new.p99_ns as f64);
+            Some(Comparison {
+                structure: new.structure.clone(),
+                n: new.n,
+                workload: new.workload.clone(),
+                phase: new.phase.clone(),
+                throughput_change,
+                p99_change,
+                regression: throughput_change.is_some_and(|change| change < -threshold)
+                    || p99_change.is_some_and(|change| change > threshold),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use crate::{
+        avl::AvlSet,
+        workload::{self, Workload},
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_round_trip_and_compare() {
+        let result = workload::run(&mut AvlSet::default(), &Workload::random(1000));
+        let records = BenchRecord::from_result("avl", 1000, &result);
+        assert_eq!(3, records.len());
+        assert_eq!(
+            "uniform/phased/mix-1:10:1/universe-3000",
+            records[0].workload
+        );
+        assert_eq!("insert", records[0].phase);
+
+        let dir = env::temp_dir();
+        for extension in ["json", "csv"] {
+            let path = dir.join(format!("bench_round_trip_{}.{extension}", process::id()));
+            write_records(&path, &records).unwrap();
+            let read = read_records(&path).unwrap();
+            fs::remove_file(&path).unwrap();
+            assert_eq!(records.len(), read.len());
+            for (written, read) in records.iter().zip(&read) {
+                assert_eq!(written.key(), read.key());
+                assert_eq!(written.p99_ns, read.p99_ns);
+                assert!((written.ops_per_sec - read.ops_per_sec).abs() < 0.1);
+            }
+        }
+
+        let mut slower = records.clone();
+        slower[1].ops_per_sec *= 0.5;
+        slower[2].p99_ns *= 2;
+        slower[2].phase = "elsewhere".to_string();
+        let comparisons = compare(&records, &slower, 0.1);
+        assert_eq!(2, comparisons.len());
+        assert!(!comparisons[0].regression);
+        assert!(comparisons[1].regression);
+        assert!((comparisons[1].throughput_change.unwrap() + 0.5).abs() < 1e-9);
+
+        let mut empty = records.clone();
+        empty[0].ops_per_sec = 0.0;
+        empty[0].p99_ns = 0;
+        let comparisons = compare(&empty, &records, 0.1);
+        assert_eq!(None, comparisons[0].throughput_change);
+        assert_eq!(None, comparisons[0].p99_change);
+        assert!(!comparisons[0].regression);
+    }
+}
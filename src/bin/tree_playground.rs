@@ -0,0 +1,439 @@
+use std::{
+    collections::HashMap,
+    env,
+    fs::File,
+    io::{self, BufRead, Write},
+    process,
+    time::{Duration, Instant},
+};
+
+use rand::Rng;
+use tree_playground::{
+    benchmark::{self, BenchRecord},
+    memory::CountingAllocator,
+    minimize::{self, first_divergence, Oracle},
+    oplog::{self, Recorder},
+    play_workload,
+    registry::{self, TreeFactory, TREES},
+    repl::Session,
+    sorted_percent,
+    tester::{self, ConsoleReporter, JUnitReporter, JsonReporter, Pattern, Reporter, RunOptions},
+    viz::draw,
+    workload::{KeyDistribution, Schedule, Workload},
+};
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+const USAGE: &str = "\
+Usage: tree_playground <command> [options]
+
+Commands:
+  bench   [--tree avl,treap] [--n 1e3,1e6] [--workload random,sorted,zipf-1.2,...]
+          [--schedule phased|interleaved] [--universe N] [--seed N] [--out results.json|csv]
+  sort    [--tree avl-multiset] [--time-limit seconds] [--json report.json] [--junit report.xml]
+          [--inputs 'test.*.in'] [--outputs 'test.*.out']
+          <dir with test.N.in/test.N.out>
+  viz     [--tree avl] [--n N | key...]
+  repl    [--tree avl]
+  compare <old.json|csv> <new.json|csv> [threshold %, default 5]
+  record  [--tree avl] [--n 1e5] [--workload random] [--schedule ...] <op log>
+  replay  [--tree bst,avl,treap] <op log>
+  minimize [--tree avl] [--name test_divergence] [--out minimal.log] <op log>
+  trees   list the available trees
+
+Workloads are random, sorted or a key distribution: uniform, sequential, reverse,
+zipf[-exponent], clustered[-clustersxwidth], sawtooth[-period].";
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let command = args.next().unwrap_or_default();
+    let args = Args::parse(args);
+    let result = match command.as_str() {
+        "bench" => bench(&args),
+        "sort" => sort(&args),
+        "viz" => viz(&args),
+        "repl" => repl(&args),
+        "compare" => compare(&args),
+        "record" => record(&args),
+        "replay" => replay(&args),
+        "minimize" => minimize_log(&args),
+        "trees" => {
+            for factory in TREES {
+                println!("{:<18}{}", factory.name, factory.description);
+            }
+            Ok(())
+        }
+        _ => Err(format!("unknown command {command:?}")),
+    };
+    if let Err(error) = result {
+        eprintln!("{error}\n\n{USAGE}");
+        process::exit(2);
+    }
+}
+
+/// `--name value` options and everything else in order.
+struct Args {
+    options: HashMap<String, String>,
+    positional: Vec<String>,
+}
+
+impl Args {
+    fn parse(mut args: impl Iterator<Item = String>) -> Self {
+        let mut options = HashMap::new();
+        let mut positional = vec![];
+        while let Some(arg) = args.next() {
+            match arg.strip_prefix("--") {
+                Some(name) => {
+                    options.insert(name.to_string(), args.next().unwrap_or_default());
+                }
+                None => positional.push(arg),
+            }
+        }
+        Self {
+            options,
+            positional,
+        }
+    }
+
+    fn get(&self, name: &str) -> Option<&str> {
+        self.options.get(name).map(String::as_str)
+    }
+
+    fn list(&self, name: &str, default: &'static str) -> Vec<&str> {
+        self.get(name)
+            .unwrap_or(default)
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .collect()
+    }
+
+    fn tree(&self, default: &str) -> Result<&'static TreeFactory, String> {
+        let name = self.get("tree").unwrap_or(default);
+        registry::find(name).ok_or_else(|| format!("unknown tree {name:?}"))
+    }
+}
+
+/// Accepts `1000000`, `1_000_000` and `1e6`.
+fn parse_count(arg: &str) -> Result<usize, String> {
+    let arg = arg.replace('_', "");
+    arg.parse::<usize>()
+        .ok()
+        .or_else(|| {
+            arg.parse::<f64>()
+                .ok()
+                .filter(|n| n.is_finite() && *n >= 0.0 && n.fract() == 0.0)
+                .map(|n| n as usize)
+        })
+        .ok_or_else(|| format!("cannot parse {arg:?} as a count"))
+}
+
+fn workload(name: &str, n: usize, args: &Args) -> Result<Workload, String> {
+    let mut workload = match name {
+        "random" => Workload::random(n),
+        "sorted" => Workload::sorted(n),
+        distribution => Workload {
+            distribution: distribution.parse::<KeyDistribution>()?,
+            ..Workload::random(n)
+        },
+    };
+    if let Some(schedule) = args.get("schedule") {
+        workload.schedule = schedule.parse::<Schedule>()?;
+    }
+    if let Some(universe) = args.get("universe") {
+        workload.universe = parse_count(universe)? as u64;
+    }
+    if let Some(seed) = args.get("seed") {
+        workload.seed = seed
+            .parse()
+            .map_err(|_| format!("cannot parse seed {seed:?}"))?;
+    }
+    workload.validate()?;
+    Ok(workload)
+}
+
+fn bench(args: &Args) -> Result<(), String> {
+    let trees = registry::find_all(args.get("tree").unwrap_or("avl"))?;
+    let sizes = args
+        .list("n", "1e6")
+        .into_iter()
+        .map(parse_count)
+        .collect::<Result<Vec<_>, _>>()?;
+    let workloads = args.list("workload", "random");
+    let mut records = vec![];
+    for factory in trees {
+        println!("{}", "=".repeat(80));
+        println!("START PLAY WITH {}", factory.description.to_uppercase());
+        for &n in &sizes {
+            for name in &workloads {
+                let workload = workload(name, n, args)?;
+                println!("{}, n = {n}", workload.name());
+                let mut tree = (factory.build)();
+                let result = play_workload(tree.as_mut(), &workload);
+                let values = tree.values();
+                if !values.is_empty() {
+                    println!("Tree is {}% sorted.", sorted_percent(values.into_iter()));
+                }
+                records.extend(BenchRecord::from_result(factory.name, n, &result));
+                println!();
+            }
+            println!("{}", "-".repeat(80));
+        }
+    }
+    if let Some(path) = args.get("out") {
+        benchmark::write_records(path, &records).map_err(|error| format!("{path}: {error}"))?;
+        println!("Wrote {} records to {path}", records.len());
+    }
+    Ok(())
+}
+
+fn sort(args: &Args) -> Result<(), String> {
+    let factory = args.tree("avl-multiset")?;
+    let [dir] = args.positional.as_slice() else {
+        return Err("sort needs exactly one directory".to_string());
+    };
+    let solver = move |data: Vec<&str>| {
+        let mut tree = (factory.build)();
+        let start = Instant::now();
+        let mut n = 0;
+        for number in data[1].split(' ').filter_map(|x| x.parse::<i64>().ok()) {
+            tree.insert(number);
+            n += 1;
+        }
+        let elapsed = Instant::now().duration_since(start);
+        println!("Inserted {n} numbers in {elapsed:?}");
+        tree.values()
+            .iter()
+            .map(|x| x.to_string())
+            .collect::<Vec<String>>()
+            .join(" ")
+    };
+    let time_limit = match args.get("time-limit") {
+        Some(seconds) => Some(
+            seconds
+                .parse::<f64>()
+                .ok()
+                .filter(|seconds| seconds.is_finite() && *seconds > 0.0)
+                .map(Duration::from_secs_f64)
+                .ok_or_else(|| format!("cannot parse time limit {seconds:?}"))?,
+        ),
+        None => None,
+    };
+    let create = |path: &str| File::create(path).map_err(|error| format!("{path}: {error}"));
+    let mut console = ConsoleReporter { verbose: true };
+    let mut json = args
+        .get("json")
+        .map(create)
+        .transpose()?
+        .map(JsonReporter::new);
+    let mut junit = args
+        .get("junit")
+        .map(create)
+        .transpose()?
+        .map(JUnitReporter::new);
+    let mut reporters: Vec<&mut dyn Reporter> = vec![&mut console];
+    if let Some(json) = json.as_mut() {
+        reporters.push(json);
+    }
+    if let Some(junit) = junit.as_mut() {
+        reporters.push(junit);
+    }
+    let pattern = match (args.get("inputs"), args.get("outputs")) {
+        (None, None) => Pattern::default(),
+        (inputs, outputs) => Pattern::new(
+            inputs.unwrap_or("test.*.in"),
+            outputs.unwrap_or("test.*.out"),
+        )?,
+    };
+    let options = RunOptions {
+        pattern,
+        time_limit,
+        max_tests: None,
+    };
+    let report = tester::run_test_with_path(dir, solver, &options, &mut reporters)
+        .map_err(|error| format!("cannot run the tests in {dir}: {error}"))?;
+    if !report.all_passed() {
+        process::exit(1);
+    }
+    Ok(())
+}
+
+fn viz(args: &Args) -> Result<(), String> {
+    let factory = args.tree("avl")?;
+    let mut tree = (factory.build)();
+    let keys = match args.get("n") {
+        Some(n) => {
+            let n = parse_count(n)?;
+            let mut rng = rand::thread_rng();
+            (0..n).map(|_| rng.gen_range(0..4 * n as i64)).collect()
+        }
+        None => args
+            .positional
+            .iter()
+            .map(|key| key.parse().map_err(|_| format!("cannot parse key {key:?}")))
+            .collect::<Result<Vec<i64>, _>>()?,
+    };
+    for key in keys {
+        tree.insert(key);
+    }
+    println!("{}", draw(tree.as_ref(), &[]));
+    Ok(())
+}
+
+fn repl(args: &Args) -> Result<(), String> {
+    let mut session = Session::new(args.tree("avl")?);
+    println!("{}, type help for the commands", session.name());
+    println!("{}", draw(session.tree(), &[]));
+    let stdin = io::stdin();
+    loop {
+        print!("> ");
+        io::stdout().flush().map_err(|error| error.to_string())?;
+        let mut line = String::new();
+        if stdin
+            .lock()
+            .read_line(&mut line)
+            .map_err(|error| error.to_string())?
+            == 0
+        {
+            return Ok(());
+        }
+        match session.execute(&line) {
+            Some(output) => println!("{output}"),
+            None => return Ok(()),
+        }
+    }
+}
+
+fn compare(args: &Args) -> Result<(), String> {
+    let (old, new, threshold) = match args.positional.as_slice() {
+        [old, new] => (old, new, 5.0),
+        [old, new, threshold] => (
+            old,
+            new,
+            threshold
+                .parse::<f64>()
+                .map_err(|_| format!("cannot parse threshold {threshold:?}"))?,
+        ),
+        _ => return Err("compare needs two result files".to_string()),
+    };
+    let read =
+        |path: &str| benchmark::read_records(path).map_err(|error| format!("{path}: {error}"));
+    let comparisons = benchmark::compare(&read(old)?, &read(new)?, threshold / 100.0);
+    let percent = |change: Option<f64>| match change {
+        Some(change) => format!("{:+7.1}%", change * 100.0),
+        None => "no baseline".to_string(),
+    };
+    for comparison in &comparisons {
+        println!(
+            "{:<16} n = {:<9} {:<48} {:<7} throughput {}, p99 {}{}",
+            comparison.structure,
+            comparison.n,
+            comparison.workload,
+            comparison.phase,
+            percent(comparison.throughput_change),
+            percent(comparison.p99_change),
+            if comparison.regression {
+                "  REGRESSION"
+            } else {
+                ""
+            }
+        );
+    }
+    let regressions = comparisons.iter().filter(|c| c.regression).count();
+    println!(
+        "Compared {} records, {regressions} regressions.",
+        comparisons.len()
+    );
+    if regressions > 0 {
+        process::exit(1);
+    }
+    Ok(())
+}
+
+fn record(args: &Args) -> Result<(), String> {
+    let factory = args.tree("avl")?;
+    let [path] = args.positional.as_slice() else {
+        return Err("record needs exactly one log file".to_string());
+    };
+    let n = parse_count(args.get("n").unwrap_or("1e5"))?;
+    let workload = workload(args.get("workload").unwrap_or("random"), n, args)?;
+    let mut recorder = Recorder::new((factory.build)());
+    play_workload(&mut recorder, &workload);
+    let (_, entries) = recorder.into_parts();
+    oplog::write_log(path, &entries).map_err(|error| format!("{path}: {error}"))?;
+    println!("Recorded {} operations to {path}", entries.len());
+    Ok(())
+}
+
+fn replay(args: &Args) -> Result<(), String> {
+    let trees = registry::find_all(args.get("tree").unwrap_or("bst,avl,treap"))?;
+    let [path] = args.positional.as_slice() else {
+        return Err("replay needs exactly one log file".to_string());
+    };
+    let entries = oplog::read_log(path).map_err(|error| format!("{path}: {error}"))?;
+    let mut diverged = false;
+    for factory in trees {
+        let result = oplog::replay((factory.build)().as_mut(), &entries);
+        println!(
+            "{:<16} {} operations in {:?}: {:.0} ops/sec, {} mismatches",
+            factory.name,
+            result.operations,
+            result.elapsed,
+            result.ops_per_sec(),
+            result.mismatches.len()
+        );
+        println!("    latency: {}", result.latency.summary());
+        for mismatch in result.mismatches.iter().take(5) {
+            match mismatch.removed {
+                Some(removed) => println!(
+                    "    operation #{}: `{}` removed {removed}",
+                    mismatch.index, mismatch.entry
+                ),
+                None => println!(
+                    "    operation #{}: `{}` returned {}",
+                    mismatch.index,
+                    mismatch.entry,
+                    u8::from(mismatch.actual)
+                ),
+            }
+        }
+        diverged |= !result.mismatches.is_empty();
+    }
+    if diverged {
+        process::exit(1);
+    }
+    Ok(())
+}
+
+fn minimize_log(args: &Args) -> Result<(), String> {
+    let factory = args.tree("avl")?;
+    let [path] = args.positional.as_slice() else {
+        return Err("minimize needs exactly one log file".to_string());
+    };
+    let entries = oplog::read_log(path).map_err(|error| format!("{path}: {error}"))?;
+    let oracle = Oracle::keeping_duplicates(factory.keeps_duplicates);
+    let Some(first) = first_divergence(oracle, factory.build, &entries) else {
+        println!(
+            "{} agrees with {oracle} on all {} operations.",
+            factory.name,
+            entries.len()
+        );
+        return Ok(());
+    };
+    println!(
+        "{} diverges from {oracle} at operation #{first}.",
+        factory.name
+    );
+    let minimal = minimize::minimize(oracle, factory.build, &entries).unwrap();
+    println!("Minimized to {} operations:\n", minimal.len());
+    let name = args.get("name").unwrap_or("test_divergence");
+    println!(
+        "{}",
+        minimize::to_test(oracle, name, factory.type_name, &minimal)
+    );
+    if let Some(out) = args.get("out") {
+        oplog::write_log(out, &minimal).map_err(|error| format!("{out}: {error}"))?;
+    }
+    Ok(())
+}
@@ -1,11 +1,17 @@
-use std::{cmp::Ordering, mem::replace};
+use std::{cmp::Ordering, mem::replace, vec};
 
-use crate::Tree;
+use crate::{
+    comparator::Comparator,
+    memory::HeapSize,
+    sorted_set::{build_balanced, ordered_set_impls},
+    viz::Shape,
+    Tree,
+};
 
 type BSTree = Option<Box<BstNode>>;
 
 #[derive(Debug, Clone, PartialEq)]
-struct BstNode {
+pub(crate) struct BstNode {
     value: i64,
     left: BSTree,
     right: BSTree,
@@ -19,18 +25,45 @@ impl BstNode {
             right: None,
         }
     }
+
+    fn join(value: i64, left: BSTree, right: BSTree) -> Box<Self> {
+        Box::new(Self { value, left, right })
+    }
 }
 
-#[derive(Debug, Default, Clone, PartialEq)]
+#[derive(Debug, Default, Clone)]
 pub struct BstSet {
     root: BSTree,
+    cmp: Comparator,
+}
+
+impl BstSet {
+    pub fn with_comparator(
+        compare: impl Fn(&i64, &i64) -> Ordering + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            root: None,
+            cmp: Comparator::new(compare),
+        }
+    }
+}
+
+fn shape(tree: &BSTree) -> Option<Shape> {
+    tree.as_ref().map(|node| {
+        Shape::new(
+            node.value,
+            node.value.to_string(),
+            shape(&node.left),
+            shape(&node.right),
+        )
+    })
 }
 
 impl Tree for BstSet {
     fn insert(&mut self, value: i64) -> bool {
         let mut current = &mut self.root;
         while let Some(node) = current {
-            match node.value.cmp(&value) {
+            match self.cmp.compare(&node.value, &value) {
                 Ordering::Less => current = &mut node.right,
                 Ordering::Equal => return false,
                 Ordering::Greater => current = &mut node.left,
@@ -43,7 +76,7 @@ impl Tree for BstSet {
     fn search(&self, value: i64) -> bool {
         let mut current = &self.root;
         while let Some(node) = current {
-            match node.value.cmp(&value) {
+            match self.cmp.compare(&node.value, &value) {
                 Ordering::Less => current = &node.right,
                 Ordering::Equal => return true,
                 Ordering::Greater => current = &node.left,
@@ -54,6 +87,7 @@ impl Tree for BstSet {
 
     fn remove(&mut self, value: i64) -> Option<i64> {
         fn find_target<'a>(
+            cmp: &Comparator,
             value: i64,
             start: &'a mut BSTree,
             prev_ptrs: &mut Vec<*mut BstNode>,
@@ -61,7 +95,7 @@ impl Tree for BstSet {
             let mut target = None;
             let mut current = start;
             while let Some(node) = current {
-                match node.value.cmp(&value) {
+                match cmp.compare(&node.value, &value) {
                     Ordering::Less => {
                         prev_ptrs.push(&mut **node);
                         current = &mut node.right;
@@ -151,7 +185,7 @@ impl Tree for BstSet {
         }
 
         let mut prev_ptrs = Vec::<*mut BstNode>::new();
-        let target = find_target(value, &mut self.root, &mut prev_ptrs)?;
+        let target = find_target(&self.cmp, value, &mut self.root, &mut prev_ptrs)?;
         let taken_value = if zero_or_one_child(target) {
             match take_value_when_zero_or_one_child(target, prev_ptrs.pop()) {
                 Some(value) => value,
@@ -162,6 +196,14 @@ impl Tree for BstSet {
         };
         Some(taken_value)
     }
+
+    fn values(&self) -> Vec<i64> {
+        self.iter().copied().collect()
+    }
+
+    fn shape(&self) -> Option<Shape> {
+        shape(&self.root)
+    }
 }
 
 impl<'a> BstSet {
@@ -210,8 +252,81 @@ impl<'a> Iterator for BstIter<'a> {
     }
 }
 
+impl BstSet {
+    pub fn drain(&mut self) -> BstIntoIter {
+        BstIntoIter {
+            prev_nodes: vec![],
+            current: self.root.take(),
+        }
+    }
+
+    pub fn retain(&mut self, mut f: impl FnMut(&i64) -> bool) {
+        let kept = self.drain().filter(|value| f(value)).collect::<Vec<i64>>();
+        self.root = build_balanced(&kept, &BstNode::join);
+    }
+
+    /// Removes the values matching `pred` right away and returns them in order.
+    pub fn extract_if(&mut self, mut pred: impl FnMut(&i64) -> bool) -> vec::IntoIter<i64> {
+        let (extracted, kept): (Vec<i64>, Vec<i64>) = self.drain().partition(|value| pred(value));
+        self.root = build_balanced(&kept, &BstNode::join);
+        extracted.into_iter()
+    }
+
+    // A balanced set with the comparator of `self`, so that set operations
+    // do not degrade into a list.
+    fn with_sorted(&self, sorted: &[i64]) -> Self {
+        Self {
+            root: build_balanced(sorted, &BstNode::join),
+            cmp: self.cmp.clone(),
+        }
+    }
+}
+
+ordered_set_impls!(BstSet, BstIter);
+
+impl IntoIterator for BstSet {
+    type Item = i64;
+    type IntoIter = BstIntoIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        BstIntoIter {
+            prev_nodes: vec![],
+            current: self.root,
+        }
+    }
+}
+
+/// Owning in-order iterator. Frees nodes as it goes, without recursion.
+#[derive(Debug)]
+pub struct BstIntoIter {
+    prev_nodes: Vec<BstNode>,
+    current: BSTree,
+}
+
+impl Iterator for BstIntoIter {
+    type Item = i64;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(mut node) = self.current.take() {
+            self.current = node.left.take();
+            self.prev_nodes.push(*node);
+        }
+        let mut node = self.prev_nodes.pop()?;
+        self.current = node.right.take();
+        Some(node.value)
+    }
+}
+
+impl HeapSize for BstSet {
+    fn heap_size_bytes(&self) -> usize {
+        self.iter().count() * size_of::<BstNode>() + self.cmp.heap_size_bytes()
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use std::hash::{Hash, Hasher};
+
     use super::*;
 
     #[test]
@@ -244,6 +359,83 @@ mod tests {
         println!("{tree:#?}");
     }
 
+    #[test]
+    fn test_comparator() {
+        let mut tree = BstSet::with_comparator(|a, b| b.cmp(a));
+        for value in [42, 15, 23, 55] {
+            tree.insert(value);
+        }
+        assert!(!tree.insert(23));
+        assert!(tree.search(15));
+        assert_eq!(Some(42), tree.remove(42));
+        let sorted = tree.iter().copied().collect::<Vec<i64>>();
+        assert_eq!(vec![55, 23, 15], sorted);
+    }
+
+    #[test]
+    fn test_collection_traits() {
+        let tree = prepare_tree();
+        let same = [96, 8, 14, 12, 18, 25, 48, 55, 21, 15, 42]
+            .into_iter()
+            .collect::<BstSet>();
+        assert_ne!(format!("{tree:?}"), format!("{same:?}"));
+        assert_eq!(tree, same);
+        let mut reversed = BstSet::with_comparator(|a, b| b.cmp(a));
+        reversed.extend(tree.iter());
+        assert_eq!(tree, reversed);
+        let hash = |set: &BstSet| {
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            set.hash(&mut hasher);
+            hasher.finish()
+        };
+        assert_eq!(hash(&tree), hash(&reversed));
+        let small = [8, 12, 14].into_iter().collect::<BstSet>();
+        assert!(small < tree);
+        assert!(reversed > small);
+        assert_eq!(
+            vec![8, 12, 14, 15, 18, 21, 25, 42, 48, 55, 96],
+            tree.clone().into_iter().collect::<Vec<i64>>()
+        );
+        let mut other = BstSet::default();
+        other.extend(&[1, 12, 42, 100]);
+        assert_eq!(
+            vec![1, 8, 12, 14, 15, 18, 21, 25, 42, 48, 55, 96, 100],
+            (&tree | &other).into_iter().collect::<Vec<i64>>()
+        );
+        assert_eq!(
+            vec![12, 42],
+            (&tree & &other).into_iter().collect::<Vec<i64>>()
+        );
+        assert_eq!(
+            vec![1, 100],
+            (&other - &tree).into_iter().collect::<Vec<i64>>()
+        );
+    }
+
+    fn depth(tree: &BSTree) -> usize {
+        tree.as_ref()
+            .map_or(0, |node| 1 + depth(&node.left).max(depth(&node.right)))
+    }
+
+    #[test]
+    fn test_retain_extract() {
+        // Sorted inserts leave a plain BST as a list; retain rebuilds it.
+        let mut tree = (0..100).collect::<BstSet>();
+        assert_eq!(100, depth(&tree.root));
+        tree.retain(|x| x % 2 == 0);
+        assert_eq!(6, depth(&tree.root));
+        assert_eq!(
+            (0..100).step_by(2).collect::<Vec<i64>>(),
+            tree.iter().copied().collect::<Vec<i64>>()
+        );
+        let extracted = tree.extract_if(|x| *x >= 64).collect::<Vec<i64>>();
+        assert_eq!((64..100).step_by(2).collect::<Vec<i64>>(), extracted);
+        assert_eq!(6, depth(&tree.root));
+        assert!(!tree.search(64));
+        assert_eq!(32, tree.drain().count());
+        assert_eq!(0, depth(&tree.root));
+    }
+
     fn prepare_minimal_tree() -> BstSet {
         let mut tree = BstSet::default();
         tree.insert(42);
@@ -0,0 +1,62 @@
+use std::{cmp::Ordering, fmt, sync::Arc};
+
+use crate::memory::{HeapSize, ARC_COUNTERS};
+
+type CompareFn = dyn Fn(&i64, &i64) -> Ordering + Send + Sync;
+
+/// Runtime ordering of keys. The default is the natural order of `i64`.
+///
+/// Two comparators are equal when both are natural or both share the same
+/// closure.
+#[derive(Clone, Default)]
+pub struct Comparator(Option<Arc<CompareFn>>);
+
+impl Comparator {
+    pub fn new(compare: impl Fn(&i64, &i64) -> Ordering + Send + Sync + 'static) -> Self {
+        Self(Some(Arc::new(compare)))
+    }
+
+    pub(crate) const fn natural() -> Self {
+        Self(None)
+    }
+
+    pub fn reverse() -> Self {
+        Self::new(|a, b| b.cmp(a))
+    }
+
+    #[inline]
+    pub fn compare(&self, a: &i64, b: &i64) -> Ordering {
+        match self.0 {
+            None => a.cmp(b),
+            Some(ref compare) => compare(a, b),
+        }
+    }
+}
+
+impl fmt::Debug for Comparator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            None => f.write_str("Comparator(natural)"),
+            Some(_) => f.write_str("Comparator(custom)"),
+        }
+    }
+}
+
+impl PartialEq for Comparator {
+    fn eq(&self, other: &Self) -> bool {
+        match (&self.0, &other.0) {
+            (None, None) => true,
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+/// A custom comparator keeps its closure in an `Arc` shared by every clone.
+impl HeapSize for Comparator {
+    fn heap_size_bytes(&self) -> usize {
+        self.0
+            .as_ref()
+            .map_or(0, |compare| ARC_COUNTERS + size_of_val(&**compare))
+    }
+}
@@ -0,0 +1,220 @@
+use std::{
+    fmt,
+    sync::{atomic::Ordering, Mutex, RwLock},
+};
+
+use crossbeam_epoch::{self as epoch, Atomic, Owned};
+
+use crate::Tree;
+
+/// Tree that many threads can use through a shared reference.
+pub trait SharedTree: Send + Sync {
+    fn insert(&self, value: i64) -> bool;
+    fn remove(&self, value: i64) -> Option<i64>;
+    fn search(&self, value: i64) -> bool;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mutation {
+    Insert(i64),
+    Remove(i64),
+}
+
+fn apply(tree: &mut dyn Tree, mutations: impl IntoIterator<Item = Mutation>) -> usize {
+    mutations
+        .into_iter()
+        .filter(|mutation| match *mutation {
+            Mutation::Insert(value) => tree.insert(value),
+            Mutation::Remove(value) => tree.remove(value).is_some(),
+        })
+        .count()
+}
+
+/// Any `Tree` behind a `RwLock`: searches run in parallel, mutations take turns.
+#[derive(Debug, Default)]
+pub struct ConcurrentSet<T: Tree> {
+    tree: RwLock<T>,
+}
+
+impl<T: Tree> ConcurrentSet<T> {
+    pub fn new(tree: T) -> Self {
+        Self {
+            tree: RwLock::new(tree),
+        }
+    }
+
+    /// Applies all `mutations` under one write lock and returns how many of them
+    /// changed the tree.
+    pub fn batch(&self, mutations: impl IntoIterator<Item = Mutation>) -> usize {
+        apply(&mut *self.tree.write().unwrap(), mutations)
+    }
+
+    pub fn into_inner(self) -> T {
+        self.tree.into_inner().unwrap()
+    }
+}
+
+impl<T: Tree + Send + Sync> SharedTree for ConcurrentSet<T> {
+    fn insert(&self, value: i64) -> bool {
+        self.tree.write().unwrap().insert(value)
+    }
+
+    fn remove(&self, value: i64) -> Option<i64> {
+        self.tree.write().unwrap().remove(value)
+    }
+
+    fn search(&self, value: i64) -> bool {
+        self.tree.read().unwrap().search(value)
+    }
+}
+
+/// Copy-on-write wrapper for trees with O(1) `clone`, such as
+/// `PersistentAvlSet` and `PersistentTreapSet`.
+///
+/// The current version sits behind an atomic pointer. Readers load it under
+/// an epoch guard and search it in place, without locks and without waiting
+/// for writers. Writers take turns: each one clones the current version,
+/// changes the copy and swaps it in, and the old version is freed once no
+/// reader can still see it.
+///
+/// Every write and every `snapshot` clones the tree. For persistent trees
+/// that is a pointer copy, but an ordinary tree such as `AvlSet` would be
+/// copied in full on each update, so use `ConcurrentSet` for those.
+pub struct SnapshotSet<T: Tree + Clone> {
+    current: Atomic<T>,
+    writer: Mutex<()>,
+}
+
+impl<T: Tree + Clone + Send + Sync> SnapshotSet<T> {
+    pub fn new(tree: T) -> Self {
+        Self {
+            current: Atomic::new(tree),
+            writer: Mutex::new(()),
+        }
+    }
+
+    /// The current version. It stays unchanged whatever writers do later.
+    pub fn snapshot(&self) -> T {
+        self.read(T::clone)
+    }
+
+    /// Applies all `mutations` as one new version and returns how many of them
+    /// changed the tree.
+    pub fn batch(&self, mutations: impl IntoIterator<Item = Mutation>) -> usize {
+        self.update(|tree| apply(tree, mutations))
+    }
+
+    fn read<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        let guard = &epoch::pin();
+        // The pointer is never null, and a swapped out version is destroyed
+        // only after every guard pinned before the swap is gone.
+        f(unsafe { self.current.load(Ordering::Acquire, guard).deref() })
+    }
+
+    fn update<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        let _writer = self.writer.lock().unwrap();
+        let mut next = self.snapshot();
+        let result = f(&mut next);
+        let guard = &epoch::pin();
+        let previous = self.current.swap(Owned::new(next), Ordering::AcqRel, guard);
+        unsafe { guard.defer_destroy(previous) };
+        result
+    }
+}
+
+impl<T: Tree + Clone + Send + Sync + Default> Default for SnapshotSet<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: Tree + Clone> fmt::Debug for SnapshotSet<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SnapshotSet").finish_non_exhaustive()
+    }
+}
+
+impl<T: Tree + Clone> Drop for SnapshotSet<T> {
+    fn drop(&mut self) {
+        // `&mut self` rules out readers, so the version can go right away.
+        unsafe {
+            let guard = epoch::unprotected();
+            drop(self.current.load(Ordering::Relaxed, guard).into_owned());
+        }
+    }
+}
+
+impl<T: Tree + Clone + Send + Sync> SharedTree for SnapshotSet<T> {
+    fn insert(&self, value: i64) -> bool {
+        self.update(|tree| tree.insert(value))
+    }
+
+    fn remove(&self, value: i64) -> Option<i64> {
+        self.update(|tree| tree.remove(value))
+    }
+
+    fn search(&self, value: i64) -> bool {
+        self.read(|tree| tree.search(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use crate::{avl::AvlSet, persistent::PersistentAvlSet};
+
+    use super::*;
+
+    fn hammer(tree: &dyn SharedTree) {
+        thread::scope(|scope| {
+            for writer in 0..4_i64 {
+                scope.spawn(move || {
+                    for value in (writer * 1000)..(writer * 1000 + 500) {
+                        assert!(tree.insert(value));
+                    }
+                    for value in (writer * 1000)..(writer * 1000 + 250) {
+                        assert_eq!(Some(value), tree.remove(value));
+                    }
+                });
+            }
+            scope.spawn(|| {
+                for value in 0..4000 {
+                    tree.search(value);
+                }
+            });
+        });
+        for writer in 0..4_i64 {
+            assert!(!tree.search(writer * 1000 + 249));
+            assert!(tree.search(writer * 1000 + 250));
+            assert!(!tree.search(writer * 1000 + 500));
+        }
+    }
+
+    #[test]
+    fn test_concurrent_set() {
+        let set = ConcurrentSet::new(AvlSet::default());
+        hammer(&set);
+        assert_eq!(
+            2,
+            set.batch([
+                Mutation::Insert(-1),
+                Mutation::Remove(250),
+                Mutation::Remove(1)
+            ])
+        );
+        assert_eq!(1000, set.into_inner().into_iter().count());
+    }
+
+    #[test]
+    fn test_snapshot_set() {
+        let set = SnapshotSet::new(PersistentAvlSet::default());
+        let empty = set.snapshot();
+        hammer(&set);
+        let before = set.snapshot();
+        set.batch([Mutation::Remove(250), Mutation::Insert(-1)]);
+        assert!(before.search(250));
+        assert!(!set.search(250));
+        assert!(empty.iter().next().is_none());
+    }
+}
@@ -0,0 +1,253 @@
+use std::{
+    cmp::Ordering,
+    error::Error,
+    fmt::{self, Debug},
+};
+
+use crate::{comparator::Comparator, Tree};
+
+/// Returned by `insert_after` of a mutable cursor when the key would not end
+/// up right after the current element.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnorderedKeyError {
+    pub key: i64,
+}
+
+impl fmt::Display for UnorderedKeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "key {} does not fit right after the cursor", self.key)
+    }
+}
+
+impl Error for UnorderedKeyError {}
+
+/// Read access to a node of a binary search tree, which is all the cursors
+/// need from the sets they walk.
+pub(crate) trait BinaryNode {
+    fn value(&self) -> &i64;
+    fn left(&self) -> Option<&dyn BinaryNode>;
+    fn right(&self) -> Option<&dyn BinaryNode>;
+}
+
+/// Set that hands out cursors: its root, its comparator, and the `Tree`
+/// updates of the mutable cursor.
+pub(crate) trait CursorSet: Tree {
+    fn root(&self) -> Option<&dyn BinaryNode>;
+    fn comparator(&self) -> &Comparator;
+}
+
+/// Smallest value, in the order of the comparator.
+pub(crate) fn first(root: Option<&dyn BinaryNode>) -> Option<&i64> {
+    let mut node = root?;
+    while let Some(left) = node.left() {
+        node = left;
+    }
+    Some(node.value())
+}
+
+/// Largest value, in the order of the comparator.
+pub(crate) fn last(root: Option<&dyn BinaryNode>) -> Option<&i64> {
+    let mut node = root?;
+    while let Some(right) = node.right() {
+        node = right;
+    }
+    Some(node.value())
+}
+
+// First value not less than `key` if `inclusive`, greater than `key` otherwise.
+fn next_from(set: &dyn CursorSet, key: i64, inclusive: bool) -> Option<i64> {
+    let mut found = None;
+    let mut current = set.root();
+    while let Some(node) = current {
+        current = match set.comparator().compare(node.value(), &key) {
+            Ordering::Equal if inclusive => return Some(*node.value()),
+            Ordering::Less | Ordering::Equal => node.right(),
+            Ordering::Greater => {
+                found = Some(*node.value());
+                node.left()
+            }
+        }
+    }
+    found
+}
+
+fn prev_before(set: &dyn CursorSet, key: i64) -> Option<i64> {
+    let mut found = None;
+    let mut current = set.root();
+    while let Some(node) = current {
+        current = match set.comparator().compare(node.value(), &key) {
+            Ordering::Less => {
+                found = Some(*node.value());
+                node.right()
+            }
+            Ordering::Equal | Ordering::Greater => node.left(),
+        }
+    }
+    found
+}
+
+/// Read-only cursor that keeps the path from the root to the current node,
+/// so stepping in either direction is amortized O(1).
+///
+/// Past either end the cursor is at the ghost position, from which
+/// `move_next` goes to the first value and `move_prev` to the last one.
+pub struct Cursor<'a> {
+    set: &'a dyn CursorSet,
+    path: Vec<&'a dyn BinaryNode>,
+}
+
+impl<'a> Cursor<'a> {
+    pub(crate) fn new(set: &'a dyn CursorSet) -> Self {
+        Self { set, path: vec![] }
+    }
+
+    /// Moves to the first value not less than `key`, or to the ghost position.
+    pub fn seek(&mut self, key: i64) {
+        self.path.clear();
+        let mut found = 0;
+        let mut current = self.set.root();
+        while let Some(node) = current {
+            self.path.push(node);
+            current = match self.set.comparator().compare(node.value(), &key) {
+                Ordering::Less => node.right(),
+                Ordering::Equal => {
+                    found = self.path.len();
+                    break;
+                }
+                Ordering::Greater => {
+                    found = self.path.len();
+                    node.left()
+                }
+            }
+        }
+        self.path.truncate(found);
+    }
+
+    pub fn peek(&self) -> Option<&'a i64> {
+        self.path.last().map(|node| node.value())
+    }
+
+    pub fn move_next(&mut self) {
+        match self.path.last() {
+            None => self.push_leftmost(self.set.root()),
+            Some(node) if node.right().is_some() => self.push_leftmost(node.right()),
+            Some(_) => self.pop_until(|parent, child| is_same(parent.left(), child)),
+        }
+    }
+
+    pub fn move_prev(&mut self) {
+        match self.path.last() {
+            None => self.push_rightmost(self.set.root()),
+            Some(node) if node.left().is_some() => self.push_rightmost(node.left()),
+            Some(_) => self.pop_until(|parent, child| is_same(parent.right(), child)),
+        }
+    }
+
+    fn push_leftmost(&mut self, mut tree: Option<&'a dyn BinaryNode>) {
+        while let Some(node) = tree {
+            self.path.push(node);
+            tree = node.left();
+        }
+    }
+
+    fn push_rightmost(&mut self, mut tree: Option<&'a dyn BinaryNode>) {
+        while let Some(node) = tree {
+            self.path.push(node);
+            tree = node.right();
+        }
+    }
+
+    // Climbs up until the node we came from is on the given side of its parent.
+    fn pop_until(&mut self, came_from: impl Fn(&dyn BinaryNode, &dyn BinaryNode) -> bool) {
+        while let Some(child) = self.path.pop() {
+            match self.path.last() {
+                Some(parent) if came_from(*parent, child) => return,
+                _ => (),
+            }
+        }
+    }
+}
+
+impl Debug for Cursor<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Cursor")
+            .field("current", &self.peek())
+            .finish_non_exhaustive()
+    }
+}
+
+fn is_same(tree: Option<&dyn BinaryNode>, node: &dyn BinaryNode) -> bool {
+    tree.is_some_and(|child| std::ptr::addr_eq(child, node))
+}
+
+/// Mutable cursor. It remembers the current value rather than a path, since any
+/// change may restructure the tree, so every step costs O(log n).
+pub struct CursorMut<'a> {
+    set: &'a mut dyn CursorSet,
+    current: Option<i64>,
+}
+
+impl<'a> CursorMut<'a> {
+    pub(crate) fn new(set: &'a mut dyn CursorSet) -> Self {
+        Self { set, current: None }
+    }
+
+    /// Moves to the first value not less than `key`, or to the ghost position.
+    pub fn seek(&mut self, key: i64) {
+        self.current = next_from(self.set, key, true);
+    }
+
+    pub fn peek(&self) -> Option<&i64> {
+        self.current.as_ref()
+    }
+
+    pub fn move_next(&mut self) {
+        self.current = self.next();
+    }
+
+    pub fn move_prev(&mut self) {
+        self.current = match self.current {
+            None => last(self.set.root()).copied(),
+            Some(value) => prev_before(self.set, value),
+        }
+    }
+
+    /// Removes the current value and moves to the next one.
+    pub fn remove_current(&mut self) -> Option<i64> {
+        let value = self.current?;
+        self.current = next_from(self.set, value, false);
+        self.set.remove(value)
+    }
+
+    /// Inserts `key` right after the current value without moving the cursor.
+    /// At the ghost position `key` becomes the new first value.
+    pub fn insert_after(&mut self, key: i64) -> Result<(), UnorderedKeyError> {
+        let cmp = self.set.comparator();
+        let after_current = self
+            .current
+            .is_none_or(|value| cmp.compare(&value, &key) == Ordering::Less);
+        let before_next = self
+            .next()
+            .is_none_or(|next| cmp.compare(&key, &next) == Ordering::Less);
+        if !(after_current && before_next) {
+            return Err(UnorderedKeyError { key });
+        }
+        self.set.insert(key);
+        Ok(())
+    }
+
+    fn next(&self) -> Option<i64> {
+        match self.current {
+            None => first(self.set.root()).copied(),
+            Some(value) => next_from(self.set, value, false),
+        }
+    }
+}
+
+impl Debug for CursorMut<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CursorMut")
+            .field("current", &self.current)
+            .finish_non_exhaustive()
+    }
+}
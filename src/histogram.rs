@@ -0,0 +1,164 @@
+use std::{fmt, time::Duration};
+
+// Values below `SUB_BUCKETS` nanoseconds are counted exactly. Above that every
+// power of two is split into `SUB_BUCKETS / 2` equal buckets, which keeps the
+// relative error under 1% over the whole `u64` range.
+const SUB_BUCKET_BITS: u32 = 8;
+const SUB_BUCKETS: u64 = 1 << SUB_BUCKET_BITS;
+const HALF: u64 = SUB_BUCKETS / 2;
+const BUCKETS: usize = (SUB_BUCKETS + (64 - SUB_BUCKET_BITS as u64) * HALF) as usize;
+
+fn index(nanos: u64) -> usize {
+    if nanos < SUB_BUCKETS {
+        return nanos as usize;
+    }
+    let shift = 64 - nanos.leading_zeros() - SUB_BUCKET_BITS;
+    let top = nanos >> shift;
+    (SUB_BUCKETS + (shift as u64 - 1) * HALF + top - HALF) as usize
+}
+
+// Largest value that lands in bucket `index`.
+fn highest(index: usize) -> u64 {
+    let index = index as u64;
+    if index < SUB_BUCKETS {
+        return index;
+    }
+    let shift = (index - SUB_BUCKETS) / HALF + 1;
+    let top = (index - SUB_BUCKETS) % HALF + HALF;
+    // The very last bucket ends at `u64::MAX`, where the shift wraps to zero.
+    ((top + 1) << shift).wrapping_sub(1)
+}
+
+/// Log-linear latency histogram in the spirit of HdrHistogram.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LatencyHistogram {
+    counts: Vec<u64>,
+    total: u64,
+    max: u64,
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self {
+            counts: vec![0; BUCKETS],
+            total: 0,
+            max: 0,
+        }
+    }
+}
+
+impl LatencyHistogram {
+    pub fn record(&mut self, latency: Duration) {
+        let nanos = u64::try_from(latency.as_nanos()).unwrap_or(u64::MAX);
+        self.counts[index(nanos)] += 1;
+        self.total += 1;
+        self.max = self.max.max(nanos);
+    }
+
+    pub fn merge(&mut self, other: &Self) {
+        for (count, other) in self.counts.iter_mut().zip(&other.counts) {
+            *count += other;
+        }
+        self.total += other.total;
+        self.max = self.max.max(other.max);
+    }
+
+    pub fn len(&self) -> u64 {
+        self.total
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.total == 0
+    }
+
+    /// Smallest latency that `percent`% of the samples do not exceed, within
+    /// the precision of the buckets.
+    pub fn percentile(&self, percent: f64) -> Duration {
+        let rank = ((percent / 100.0 * self.total as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (index, count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return Duration::from_nanos(highest(index).min(self.max));
+            }
+        }
+        self.max()
+    }
+
+    pub fn max(&self) -> Duration {
+        Duration::from_nanos(self.max)
+    }
+
+    pub fn summary(&self) -> LatencySummary {
+        LatencySummary {
+            p50: self.percentile(50.0),
+            p90: self.percentile(90.0),
+            p99: self.percentile(99.0),
+            p999: self.percentile(99.9),
+            max: self.max(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct LatencySummary {
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub p999: Duration,
+    pub max: Duration,
+}
+
+impl fmt::Display for LatencySummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "p50 {:?}, p90 {:?}, p99 {:?}, p99.9 {:?}, max {:?}",
+            self.p50, self.p90, self.p99, self.p999, self.max
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buckets() {
+        for nanos in (0..100_000).chain([u64::MAX / 3, u64::MAX]) {
+            let index = index(nanos);
+            assert!(index < BUCKETS);
+            assert!(highest(index) >= nanos);
+            assert!(index == 0 || highest(index - 1) < nanos);
+            assert!((highest(index) - nanos) as f64 <= nanos as f64 / 128.0);
+        }
+    }
+
+    #[test]
+    fn test_percentiles() {
+        let mut histogram = LatencyHistogram::default();
+        assert!(histogram.is_empty());
+        for nanos in (1..=10000).rev() {
+            histogram.record(Duration::from_nanos(nanos));
+        }
+        let close = |expected: u64, actual: Duration| {
+            let actual = actual.as_nanos() as f64;
+            assert!(
+                (actual - expected as f64).abs() <= expected as f64 / 100.0,
+                "{actual}"
+            );
+        };
+        let summary = histogram.summary();
+        close(5000, summary.p50);
+        close(9000, summary.p90);
+        close(9900, summary.p99);
+        close(9990, summary.p999);
+        assert_eq!(Duration::from_nanos(10000), summary.max);
+
+        let mut other = LatencyHistogram::default();
+        other.record(Duration::from_secs(1));
+        histogram.merge(&other);
+        assert_eq!(10001, histogram.len());
+        assert_eq!(Duration::from_secs(1), histogram.percentile(100.0));
+    }
+}
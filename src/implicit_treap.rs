@@ -0,0 +1,511 @@
+use std::{
+    cmp::min,
+    fmt::Debug,
+    mem::{swap, take},
+    ops::{Bound, RangeBounds},
+};
+
+use rand::Rng;
+
+/// Per-subtree aggregate and lazy range update for an `ImplicitTreap`.
+///
+/// A tag is applied to a node right away (its value and summary are brought up
+/// to date) and stays there as a pending update for the children until someone
+/// needs to look inside.
+pub trait Lazy<T> {
+    type Summary: Debug + Clone + PartialEq;
+    /// Pending update. `Default` is the update that changes nothing.
+    type Tag: Debug + Clone + PartialEq + Default;
+
+    fn lift(value: &T) -> Self::Summary;
+    /// Should not depend on the order of its arguments if ranges are reversed.
+    fn combine(left: &Self::Summary, right: &Self::Summary) -> Self::Summary;
+    fn apply(tag: &Self::Tag, value: &mut T);
+    fn apply_summary(tag: &Self::Tag, summary: &mut Self::Summary, size: usize);
+    /// Turns `tag` into the update that does `tag` first and `newer` after it.
+    fn compose(tag: &mut Self::Tag, newer: &Self::Tag);
+}
+
+/// No summary and no updates: a plain sequence.
+impl<T> Lazy<T> for () {
+    type Summary = ();
+    type Tag = ();
+
+    fn lift(_: &T) {}
+
+    fn combine(_: &(), _: &()) {}
+
+    fn apply(_: &(), _: &mut T) {}
+
+    fn apply_summary(_: &(), _: &mut (), _: usize) {}
+
+    fn compose(_: &mut (), _: &()) {}
+}
+
+type Treap<T, L> = Option<Box<Node<T, L>>>;
+
+#[derive(Debug, Clone, PartialEq)]
+struct Node<T, L: Lazy<T>> {
+    value: T,
+    priority: usize,
+    size: usize,
+    summary: L::Summary,
+    // Like `tag`, already applied to this node: its children are swapped, but
+    // theirs are not yet.
+    reversed: bool,
+    tag: L::Tag,
+    left: Treap<T, L>,
+    right: Treap<T, L>,
+}
+
+impl<T, L: Lazy<T>> Node<T, L> {
+    pub fn new(value: T) -> Self {
+        let mut rng = rand::thread_rng();
+        Self {
+            summary: L::lift(&value),
+            value,
+            priority: rng.gen(),
+            size: 1,
+            reversed: false,
+            tag: L::Tag::default(),
+            left: None,
+            right: None,
+        }
+    }
+
+    pub fn update(&mut self) {
+        self.size = 1 + size(&self.left) + size(&self.right);
+        let mut summary = L::lift(&self.value);
+        if let Some(left) = &self.left {
+            summary = L::combine(&left.summary, &summary);
+        }
+        if let Some(right) = &self.right {
+            summary = L::combine(&summary, &right.summary);
+        }
+        self.summary = summary;
+    }
+
+    fn apply(&mut self, tag: &L::Tag) {
+        L::apply(tag, &mut self.value);
+        L::apply_summary(tag, &mut self.summary, self.size);
+        L::compose(&mut self.tag, tag);
+    }
+
+    fn apply_reverse(&mut self) {
+        self.reversed = !self.reversed;
+        swap(&mut self.left, &mut self.right);
+    }
+
+    fn push(&mut self) {
+        let reversed = take(&mut self.reversed);
+        let tag = take(&mut self.tag);
+        let pending = tag != L::Tag::default();
+        for child in [&mut self.left, &mut self.right].into_iter().flatten() {
+            if reversed {
+                child.apply_reverse();
+            }
+            if pending {
+                child.apply(&tag);
+            }
+        }
+    }
+
+    // Children in sequence order, given the reversal pending from the
+    // ancestors.
+    fn children(&self, pending: &Pending<T, L>) -> [&Treap<T, L>; 2] {
+        if pending.reversed {
+            [&self.right, &self.left]
+        } else {
+            [&self.left, &self.right]
+        }
+    }
+
+    // What is pending for the children, given what is pending for this node.
+    fn below(&self, pending: &Pending<T, L>) -> Pending<T, L> {
+        let mut tag = self.tag.clone();
+        L::compose(&mut tag, &pending.tag);
+        Pending {
+            reversed: pending.reversed != self.reversed,
+            tag,
+        }
+    }
+}
+
+// Updates that ancestors have not pushed down yet, for read-only walks.
+struct Pending<T, L: Lazy<T>> {
+    reversed: bool,
+    tag: L::Tag,
+}
+
+impl<T, L: Lazy<T>> Default for Pending<T, L> {
+    fn default() -> Self {
+        Self {
+            reversed: false,
+            tag: L::Tag::default(),
+        }
+    }
+}
+
+fn size<T, L: Lazy<T>>(t: &Treap<T, L>) -> usize {
+    t.as_ref().map_or(0, |node| node.size)
+}
+
+// The key of a node is implicit: it is the number of nodes to the left of it,
+// so `split` goes by subtree sizes instead of comparing values.
+fn merge<T, L: Lazy<T>>(t1: Treap<T, L>, t2: Treap<T, L>) -> Treap<T, L> {
+    match (t1, t2) {
+        (None, t) | (t, None) => t,
+        (Some(mut left), Some(mut right)) => {
+            if left.priority > right.priority {
+                left.push();
+                left.right = merge(left.right, Some(right));
+                left.update();
+                Some(left)
+            } else {
+                right.push();
+                right.left = merge(Some(left), right.left);
+                right.update();
+                Some(right)
+            }
+        }
+    }
+}
+
+fn split<T, L: Lazy<T>>(t: Treap<T, L>, index: usize) -> (Treap<T, L>, Treap<T, L>) {
+    match t {
+        Some(mut root) => {
+            root.push();
+            let left_size = size(&root.left);
+            if left_size < index {
+                let (left, right) = split(root.right, index - left_size - 1);
+                root.right = left;
+                root.update();
+                (Some(root), right)
+            } else {
+                let (left, right) = split(root.left, index);
+                root.left = right;
+                root.update();
+                (left, Some(root))
+            }
+        }
+        None => (None, None),
+    }
+}
+
+fn collect<T: Clone, L: Lazy<T>>(t: &Treap<T, L>, pending: &Pending<T, L>, values: &mut Vec<T>) {
+    if let Some(node) = t {
+        let [left, right] = node.children(pending);
+        let below = node.below(pending);
+        collect(left, &below, values);
+        let mut value = node.value.clone();
+        L::apply(&pending.tag, &mut value);
+        values.push(value);
+        collect(right, &below, values);
+    }
+}
+
+// Summary of positions `[start, end)` of `t` with the pending updates
+// applied, without pushing them. Whole subtrees use their stored summary.
+fn fold<T, L: Lazy<T>>(
+    t: &Treap<T, L>,
+    pending: &Pending<T, L>,
+    start: usize,
+    end: usize,
+) -> Option<L::Summary> {
+    let node = t.as_ref()?;
+    if start == 0 && end >= node.size {
+        let mut summary = node.summary.clone();
+        L::apply_summary(&pending.tag, &mut summary, node.size);
+        return Some(summary);
+    }
+    let [left, right] = node.children(pending);
+    let below = node.below(pending);
+    let left_size = size(left);
+    let mut summary = None;
+    if start < left_size {
+        summary = fold(left, &below, start, min(end, left_size));
+    }
+    if start <= left_size && left_size < end {
+        let mut own = L::lift(&node.value);
+        L::apply_summary(&pending.tag, &mut own, 1);
+        summary = concat::<T, L>(summary, Some(own));
+    }
+    if end > left_size + 1 {
+        let right = fold(
+            right,
+            &below,
+            start.saturating_sub(left_size + 1),
+            end - left_size - 1,
+        );
+        summary = concat::<T, L>(summary, right);
+    }
+    summary
+}
+
+fn concat<T, L: Lazy<T>>(
+    left: Option<L::Summary>,
+    right: Option<L::Summary>,
+) -> Option<L::Summary> {
+    match (left, right) {
+        (Some(left), Some(right)) => Some(L::combine(&left, &right)),
+        (summary, None) | (None, summary) => summary,
+    }
+}
+
+/// Sequence with O(log n) positional insert, remove, split and concatenation.
+///
+/// With a `Lazy` parameter it also keeps a summary of every subtree and
+/// applies updates to whole ranges in O(log n).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImplicitTreap<T, L: Lazy<T> = ()> {
+    root: Treap<T, L>,
+}
+
+impl<T, L: Lazy<T>> Default for ImplicitTreap<T, L> {
+    fn default() -> Self {
+        Self { root: None }
+    }
+}
+
+impl<T, L: Lazy<T>> ImplicitTreap<T, L> {
+    pub fn len(&self) -> usize {
+        size(&self.root)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    /// Inserts `value` so that it ends up at position `index`.
+    ///
+    /// Panics if `index > len`.
+    pub fn insert_at(&mut self, index: usize, value: T) {
+        assert!(index <= self.len(), "insertion index is out of bounds");
+        let node = Some(Box::new(Node::new(value)));
+        let (left, right) = split(self.root.take(), index);
+        self.root = merge(merge(left, node), right);
+    }
+
+    pub fn push_back(&mut self, value: T) {
+        let node = Some(Box::new(Node::new(value)));
+        self.root = merge(self.root.take(), node);
+    }
+
+    pub fn remove_at(&mut self, index: usize) -> Option<T> {
+        if index >= self.len() {
+            return None;
+        }
+        let (left, right) = split(self.root.take(), index);
+        let (middle, right) = split(right, 1);
+        self.root = merge(left, right);
+        middle.map(|node| node.value)
+    }
+
+    /// Splits the sequence into `[0, index)` and `[index, len)`.
+    pub fn split_at(self, index: usize) -> (Self, Self) {
+        let (left, right) = split(self.root, index);
+        (Self { root: left }, Self { root: right })
+    }
+
+    pub fn concat(self, other: Self) -> Self {
+        Self {
+            root: merge(self.root, other.root),
+        }
+    }
+
+    pub fn reverse(&mut self, range: impl RangeBounds<usize>) {
+        self.with_range(range, |node| node.apply_reverse());
+    }
+
+    /// Applies `tag` to every element in `range`.
+    pub fn update(&mut self, range: impl RangeBounds<usize>, tag: L::Tag) {
+        self.with_range(range, |node| node.apply(&tag));
+    }
+
+    /// Summary of `range`, or `None` if the range is empty.
+    pub fn query(&self, range: impl RangeBounds<usize>) -> Option<L::Summary> {
+        let (start, end) = self.positions(range)?;
+        fold(&self.root, &Pending::default(), start, end)
+    }
+
+    pub fn to_vec(&self) -> Vec<T>
+    where
+        T: Clone,
+    {
+        let mut values = Vec::with_capacity(self.len());
+        collect(&self.root, &Pending::default(), &mut values);
+        values
+    }
+
+    // Value at `index` with the pending updates applied, without pushing them.
+    pub(crate) fn value_at(&self, index: usize) -> Option<T>
+    where
+        T: Clone,
+    {
+        let (node, tag) = self.find(index)?;
+        let mut value = node.value.clone();
+        L::apply(&tag, &mut value);
+        Some(value)
+    }
+
+    // The node at `index` together with the updates still pending for it.
+    fn find(&self, index: usize) -> Option<(&Node<T, L>, L::Tag)> {
+        let mut index = index;
+        let mut current = &self.root;
+        let mut pending = Pending::default();
+        while let Some(node) = current {
+            let [left, right] = node.children(&pending);
+            let left_size = size(left);
+            if index == left_size {
+                return Some((node, pending.tag));
+            }
+            if index < left_size {
+                current = left;
+            } else {
+                index -= left_size + 1;
+                current = right;
+            }
+            pending = node.below(&pending);
+        }
+        None
+    }
+
+    // Cuts `range` out into its own treap, lets `f` work on its root and glues
+    // the pieces back. Returns `None` when the range is empty.
+    fn with_range<R>(
+        &mut self,
+        range: impl RangeBounds<usize>,
+        f: impl FnOnce(&mut Node<T, L>) -> R,
+    ) -> Option<R> {
+        let (start, end) = self.positions(range)?;
+        let (left, right) = split(self.root.take(), start);
+        let (mut middle, right) = split(right, end - start);
+        let result = middle.as_mut().map(|node| f(node));
+        self.root = merge(merge(left, middle), right);
+        result
+    }
+
+    // `range` as `[start, end)` clamped to the sequence, or `None` if empty.
+    fn positions(&self, range: impl RangeBounds<usize>) -> Option<(usize, usize)> {
+        let start = match range.start_bound() {
+            Bound::Included(&start) => start,
+            Bound::Excluded(&start) => start.saturating_add(1),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&end) => end.saturating_add(1),
+            Bound::Excluded(&end) => end,
+            Bound::Unbounded => self.len(),
+        };
+        let end = min(end, self.len());
+        (start < end).then_some((start, end))
+    }
+}
+
+impl<T> ImplicitTreap<T> {
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.find(index).map(|(node, _)| &node.value)
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        let mut index = index;
+        let mut current = &mut self.root;
+        while let Some(node) = current {
+            node.push();
+            let left_size = size(&node.left);
+            if index < left_size {
+                current = &mut node.left;
+            } else if index == left_size {
+                return Some(&mut node.value);
+            } else {
+                index -= left_size + 1;
+                current = &mut node.right;
+            }
+        }
+        None
+    }
+
+    pub fn iter(&self) -> ImplicitTreapIter<'_, T> {
+        ImplicitTreapIter {
+            prev_nodes: vec![],
+            current: (&self.root, false),
+        }
+    }
+}
+
+impl<T, L: Lazy<T>> FromIterator<T> for ImplicitTreap<T, L> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut treap = Self::default();
+        for value in iter {
+            treap.push_back(value);
+        }
+        treap
+    }
+}
+
+#[derive(Debug)]
+pub struct ImplicitTreapIter<'a, T> {
+    // Nodes whose left part is being walked, each with the reversal still
+    // pending for it.
+    prev_nodes: Vec<(&'a Node<T, ()>, bool)>,
+    current: (&'a Treap<T, ()>, bool),
+}
+
+impl<'a, T> Iterator for ImplicitTreapIter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (tree, reversed) = self.current;
+            match tree {
+                None => {
+                    let (node, reversed) = self.prev_nodes.pop()?;
+                    let pending = Pending { reversed, tag: () };
+                    let [_, right] = node.children(&pending);
+                    self.current = (right, node.below(&pending).reversed);
+                    return Some(&node.value);
+                }
+                Some(node) => {
+                    self.prev_nodes.push((node, reversed));
+                    let pending = Pending { reversed, tag: () };
+                    let [left, _] = node.children(&pending);
+                    self.current = (left, node.below(&pending).reversed);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insert_remove() {
+        let mut rope = "helo".chars().collect::<ImplicitTreap<char>>();
+        rope.insert_at(3, 'l');
+        rope.insert_at(5, '!');
+        rope.insert_at(0, '>');
+        assert_eq!(">hello!", rope.iter().collect::<String>());
+        rope.reverse(1..6);
+        assert_eq!(">olleh!", rope.iter().collect::<String>());
+        assert_eq!(Some(&'e'), rope.get(4));
+        *rope.get_mut(1).unwrap() = 'O';
+        assert_eq!(vec!['>', 'O', 'l', 'l', 'e', 'h', '!'], rope.to_vec());
+        rope.reverse(1..6);
+        assert_eq!(Some('>'), rope.remove_at(0));
+        assert_eq!(None, rope.remove_at(6));
+        assert_eq!(Some(&'O'), rope.get(4));
+        assert_eq!(6, rope.len());
+    }
+
+    #[test]
+    fn test_split_concat() {
+        let rope = "hello world".chars().collect::<ImplicitTreap<char>>();
+        let (hello, world) = rope.split_at(6);
+        assert_eq!("hello ", hello.iter().collect::<String>());
+        assert_eq!("world", world.iter().collect::<String>());
+        let swapped = world.concat(hello);
+        assert_eq!("worldhello ", swapped.iter().collect::<String>());
+    }
+}
@@ -0,0 +1,209 @@
+use std::{
+    cmp::{max, Ordering},
+    ops::Range,
+};
+
+use crate::{
+    avl::{self, AvlNode, AvlTree},
+    link::Owned,
+    memory::HeapSize,
+    monoid::Monoid,
+};
+
+// Nodes are keyed by `(lo, hi)`.
+type Interval = (i64, i64);
+
+/// Largest `hi` in a subtree, so overlap queries can skip subtrees that end
+/// too early.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct MaxEnd;
+
+impl Monoid<Interval> for MaxEnd {
+    type Summary = i64;
+
+    fn identity() -> Self::Summary {
+        i64::MIN
+    }
+
+    fn lift((_, hi): Interval) -> Self::Summary {
+        hi
+    }
+
+    fn combine(left: &Self::Summary, right: &Self::Summary) -> Self::Summary {
+        max(*left, *right)
+    }
+}
+
+type IntervalTree = AvlTree<MaxEnd, Interval>;
+type IntervalNode = AvlNode<MaxEnd, Interval>;
+
+/// Set of half-open intervals `[lo, hi)` with overlap queries in
+/// O(log n + k) for `k` reported intervals.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct IntervalSet {
+    root: IntervalTree,
+}
+
+impl IntervalSet {
+    /// Empty intervals are rejected.
+    pub fn insert(&mut self, interval: Range<i64>) -> bool {
+        !interval.is_empty()
+            && avl::insert_by::<MaxEnd, Interval, Owned>(
+                &mut self.root,
+                (interval.start, interval.end),
+                &Ord::cmp,
+            )
+    }
+
+    pub fn remove(&mut self, interval: Range<i64>) -> bool {
+        avl::remove_by::<MaxEnd, Interval, Owned>(
+            &mut self.root,
+            (interval.start, interval.end),
+            &Ord::cmp,
+        )
+        .is_some()
+    }
+
+    pub fn contains(&self, interval: Range<i64>) -> bool {
+        let mut current = &self.root;
+        while let Some(node) = current {
+            current = match node.value.cmp(&(interval.start, interval.end)) {
+                Ordering::Less => &node.right,
+                Ordering::Equal => return true,
+                Ordering::Greater => &node.left,
+            }
+        }
+        false
+    }
+
+    /// Intervals containing `point`, ordered by `(lo, hi)`.
+    pub fn overlapping(&self, point: i64) -> Overlapping<'_> {
+        self.overlapping_range(point..point.saturating_add(1))
+    }
+
+    /// Intervals sharing at least one point with `range`, ordered by `(lo, hi)`.
+    /// An empty or reversed `range` holds no points and overlaps nothing.
+    pub fn overlapping_range(&self, range: Range<i64>) -> Overlapping<'_> {
+        let mut iter = Overlapping {
+            stack: vec![],
+            start: range.start,
+            end: range.end,
+        };
+        if !range.is_empty() {
+            iter.push_left(&self.root);
+        }
+        iter
+    }
+
+    pub fn iter(&self) -> Overlapping<'_> {
+        self.overlapping_range(i64::MIN..i64::MAX)
+    }
+}
+
+#[derive(Debug)]
+pub struct Overlapping<'a> {
+    stack: Vec<&'a IntervalNode>,
+    start: i64,
+    end: i64,
+}
+
+impl<'a> Overlapping<'a> {
+    // Subtrees whose `max_end` does not reach past `start` cannot overlap.
+    fn push_left(&mut self, mut tree: &'a IntervalTree) {
+        while let Some(node) = tree {
+            if node.summary <= self.start {
+                break;
+            }
+            self.stack.push(node);
+            tree = &node.left;
+        }
+    }
+}
+
+impl Iterator for Overlapping<'_> {
+    type Item = Range<i64>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(node) = self.stack.pop() {
+            let (lo, hi) = node.value;
+            if lo >= self.end {
+                // Everything left on the stack starts even later.
+                self.stack.clear();
+                return None;
+            }
+            self.push_left(&node.right);
+            if hi > self.start {
+                return Some(lo..hi);
+            }
+        }
+        None
+    }
+}
+
+impl HeapSize for IntervalSet {
+    fn heap_size_bytes(&self) -> usize {
+        self.iter().count() * size_of::<IntervalNode>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use super::*;
+
+    #[test]
+    fn test_overlapping() {
+        let mut set = IntervalSet::default();
+        assert!(set.insert(1..5));
+        assert!(set.insert(3..8));
+        assert!(set.insert(10..12));
+        assert!(!set.insert(3..8));
+        assert!(!set.insert(7..7));
+        assert_eq!(vec![1..5, 3..8], set.overlapping(4).collect::<Vec<_>>());
+        assert_eq!(vec![3..8], set.overlapping(5).collect::<Vec<_>>());
+        assert!(set.overlapping(9).next().is_none());
+        assert_eq!(
+            vec![3..8, 10..12],
+            set.overlapping_range(6..11).collect::<Vec<_>>()
+        );
+        assert!(set.overlapping_range(4..4).next().is_none());
+        let reversed = Range { start: 7, end: 3 };
+        assert!(set.overlapping_range(reversed).next().is_none());
+        assert!(set.remove(3..8));
+        assert!(!set.remove(3..8));
+        assert_eq!(vec![1..5, 10..12], set.iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_against_scan() {
+        let mut rng = rand::thread_rng();
+        let mut set = IntervalSet::default();
+        let mut all = vec![];
+        for _ in 0..500 {
+            let lo = rng.gen_range(0..1000);
+            let interval = lo..lo + rng.gen_range(1..50);
+            if set.insert(interval.clone()) {
+                all.push(interval);
+            }
+        }
+        let removed = all.iter().step_by(3).cloned().collect::<Vec<_>>();
+        for interval in removed.iter() {
+            assert!(set.remove(interval.clone()));
+        }
+        all.retain(|interval| !removed.contains(interval));
+        all.sort_by_key(|interval| (interval.start, interval.end));
+        assert!(set.root.as_ref().unwrap().height <= 14);
+        for _ in 0..100 {
+            let lo = rng.gen_range(0..1100);
+            // Empty and reversed queries included.
+            let query = lo..lo + rng.gen_range(-5..30);
+            let expected = all
+                .iter()
+                .filter(|x| x.start.max(query.start) < x.end.min(query.end))
+                .cloned()
+                .collect::<Vec<_>>();
+            assert_eq!(expected, set.overlapping_range(query).collect::<Vec<_>>());
+        }
+    }
+}
@@ -1,15 +1,118 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+pub mod arena;
 pub mod avl;
+pub mod benchmark;
 pub mod bst;
+pub mod comparator;
+pub mod concurrent;
+pub mod cursor;
+pub mod histogram;
+pub mod implicit_treap;
+pub mod interval;
+mod link;
+pub mod memory;
+pub mod minimize;
+pub mod monoid;
+pub mod multiset;
+pub mod oplog;
+#[cfg(feature = "rayon")]
+mod parallel;
+pub mod persistent;
 mod play;
+pub mod range_treap;
+pub mod registry;
+pub mod repl;
+pub mod skiplist;
+mod sorted_set;
 pub mod tester;
 pub mod treap;
+pub mod viz;
+pub mod workload;
 
+pub use play::play_concurrent_numbers;
 pub use play::play_random_numbers;
 pub use play::play_sorted_numbers;
+pub use play::play_workload;
 pub use play::sorted_percent;
 
 pub trait Tree {
     fn insert(&mut self, value: i64) -> bool;
     fn remove(&mut self, value: i64) -> Option<i64>;
     fn search(&self, value: i64) -> bool;
+    /// Every stored value in order, repeated as many times as it is stored.
+    fn values(&self) -> Vec<i64>;
+    /// Node layout for drawing, if the structure is a binary tree.
+    fn shape(&self) -> Option<viz::Shape> {
+        None
+    }
+}
+
+/// The standard library set, used as the oracle that other trees are
+/// checked against.
+impl Tree for BTreeSet<i64> {
+    fn insert(&mut self, value: i64) -> bool {
+        BTreeSet::insert(self, value)
+    }
+
+    fn remove(&mut self, value: i64) -> Option<i64> {
+        self.take(&value)
+    }
+
+    fn search(&self, value: i64) -> bool {
+        self.contains(&value)
+    }
+
+    fn values(&self) -> Vec<i64> {
+        self.iter().copied().collect()
+    }
+}
+
+/// Counts of every stored value: the oracle for trees that keep duplicates.
+impl Tree for BTreeMap<i64, usize> {
+    fn insert(&mut self, value: i64) -> bool {
+        *self.entry(value).or_default() += 1;
+        true
+    }
+
+    fn remove(&mut self, value: i64) -> Option<i64> {
+        let count = self.get_mut(&value)?;
+        *count -= 1;
+        if *count == 0 {
+            BTreeMap::remove(self, &value);
+        }
+        Some(value)
+    }
+
+    fn search(&self, value: i64) -> bool {
+        self.contains_key(&value)
+    }
+
+    fn values(&self) -> Vec<i64> {
+        self.iter()
+            .flat_map(|(value, count)| std::iter::repeat_n(*value, *count))
+            .collect()
+    }
+}
+
+impl<T: Tree + ?Sized> Tree for Box<T> {
+    fn insert(&mut self, value: i64) -> bool {
+        (**self).insert(value)
+    }
+
+    fn remove(&mut self, value: i64) -> Option<i64> {
+        (**self).remove(value)
+    }
+
+    fn search(&self, value: i64) -> bool {
+        (**self).search(value)
+    }
+
+    fn values(&self) -> Vec<i64> {
+        (**self).values()
+    }
+
+    fn shape(&self) -> Option<viz::Shape> {
+        (**self).shape()
+    }
 }
@@ -0,0 +1,56 @@
+use std::{ops::Deref, sync::Arc};
+
+/// How a tree node holds its children. The balancing code is written once
+/// against this trait and works both on uniquely owned nodes and on nodes
+/// shared between versions of a persistent tree.
+pub(crate) trait Link {
+    type Ptr<N>: Deref<Target = N>;
+
+    fn new<N>(node: N) -> Self::Ptr<N>;
+
+    /// Mutable access to the node. A node shared with another version is
+    /// copied first, which is all path copying comes down to.
+    fn make_mut<N: Clone>(ptr: &mut Self::Ptr<N>) -> &mut N;
+
+    fn clone_ptr<N: Clone>(ptr: &Self::Ptr<N>) -> Self::Ptr<N>;
+}
+
+/// Children in a `Box`: the tree is the only owner of its nodes.
+#[derive(Debug)]
+pub(crate) struct Owned;
+
+impl Link for Owned {
+    type Ptr<N> = Box<N>;
+
+    fn new<N>(node: N) -> Box<N> {
+        Box::new(node)
+    }
+
+    fn make_mut<N: Clone>(ptr: &mut Box<N>) -> &mut N {
+        ptr
+    }
+
+    fn clone_ptr<N: Clone>(ptr: &Box<N>) -> Box<N> {
+        ptr.clone()
+    }
+}
+
+/// Children in an `Arc`: versions share every subtree they did not touch.
+#[derive(Debug)]
+pub(crate) struct Shared;
+
+impl Link for Shared {
+    type Ptr<N> = Arc<N>;
+
+    fn new<N>(node: N) -> Arc<N> {
+        Arc::new(node)
+    }
+
+    fn make_mut<N: Clone>(ptr: &mut Arc<N>) -> &mut N {
+        Arc::make_mut(ptr)
+    }
+
+    fn clone_ptr<N: Clone>(ptr: &Arc<N>) -> Arc<N> {
+        Arc::clone(ptr)
+    }
+}
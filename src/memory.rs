@@ -0,0 +1,182 @@
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Memory a structure owns on the heap.
+pub trait HeapSize {
+    /// Bytes requested from the allocator for nodes and buffers. Allocator
+    /// overhead per block is not included.
+    fn heap_size_bytes(&self) -> usize;
+}
+
+/// The strong and weak counts stored next to the value of every `Arc`.
+pub(crate) const ARC_COUNTERS: usize = 2 * size_of::<usize>();
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+/// System allocator that keeps count of live bytes and their peak.
+///
+/// Counting is off until a binary installs it:
+///
+/// ```
+/// use tree_playground::memory::CountingAllocator;
+///
+/// #[global_allocator]
+/// static ALLOCATOR: CountingAllocator = CountingAllocator;
+/// ```
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CountingAllocator;
+
+impl CountingAllocator {
+    fn grow(size: usize) {
+        let now = ALLOCATED.fetch_add(size, Ordering::Relaxed) + size;
+        PEAK.fetch_max(now, Ordering::Relaxed);
+    }
+
+    fn shrink(size: usize) {
+        ALLOCATED.fetch_sub(size, Ordering::Relaxed);
+    }
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            Self::grow(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            Self::grow(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        Self::shrink(layout.size());
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            Self::shrink(layout.size());
+            Self::grow(new_size);
+        }
+        new_ptr
+    }
+}
+
+/// Live bytes. Stays zero unless `CountingAllocator` is installed.
+pub fn allocated_bytes() -> usize {
+    ALLOCATED.load(Ordering::Relaxed)
+}
+
+/// Highest number of live bytes since the last `reset_peak`.
+pub fn peak_bytes() -> usize {
+    PEAK.load(Ordering::Relaxed)
+}
+
+pub fn reset_peak() {
+    PEAK.store(allocated_bytes(), Ordering::Relaxed);
+}
+
+/// Starts a measurement and returns the baseline for `usage_since`.
+pub fn start_tracking() -> usize {
+    reset_peak();
+    allocated_bytes()
+}
+
+/// Bytes allocated on top of `baseline`: the most at any moment and what is
+/// still live. `None` unless `CountingAllocator` is installed.
+pub fn usage_since(baseline: usize) -> Option<MemoryUsage> {
+    let peak = peak_bytes();
+    (peak > 0).then(|| MemoryUsage {
+        peak: peak.saturating_sub(baseline),
+        retained: allocated_bytes().saturating_sub(baseline),
+    })
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryUsage {
+    pub peak: usize,
+    pub retained: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        arena::ArenaAvlSet,
+        avl::{AvlNode, AvlSet},
+        bst::{BstNode, BstSet},
+        link::Shared,
+        multiset::AvlMultiSet,
+        persistent::PersistentTreapSet,
+        skiplist::SkipList,
+        treap::{self, TreapSet},
+        Tree,
+    };
+
+    use super::*;
+
+    fn bytes_per_element<T: Tree + HeapSize + Default>() -> usize {
+        let mut small = T::default();
+        let mut large = T::default();
+        for value in 0..1000 {
+            small.insert(value);
+            large.insert(value);
+        }
+        for value in 1000..2000 {
+            large.insert(value);
+        }
+        (large.heap_size_bytes() - small.heap_size_bytes()) / 1000
+    }
+
+    #[test]
+    fn test_heap_size() {
+        assert_eq!(0, BstSet::default().heap_size_bytes());
+        assert_eq!(size_of::<BstNode>(), bytes_per_element::<BstSet>());
+        assert_eq!(size_of::<AvlNode<()>>(), bytes_per_element::<AvlSet>());
+        assert_eq!(
+            size_of::<treap::Node<()>>(),
+            bytes_per_element::<TreapSet>()
+        );
+        assert_eq!(
+            ARC_COUNTERS + size_of::<treap::Node<(), i64, Shared>>(),
+            bytes_per_element::<PersistentTreapSet>()
+        );
+        assert!(bytes_per_element::<SkipList>() >= 24);
+
+        let mut multiset = AvlMultiSet::default();
+        multiset.insert(1);
+        let single = multiset.heap_size_bytes();
+        multiset.insert(1);
+        assert_eq!(single, multiset.heap_size_bytes());
+
+        let mut arena = ArenaAvlSet::with_capacity(100);
+        let reserved = arena.heap_size_bytes();
+        for value in 0..100 {
+            arena.insert(value);
+        }
+        assert_eq!(reserved, arena.heap_size_bytes());
+    }
+
+    #[test]
+    fn test_comparator_heap_size() {
+        let mut reversed = BstSet::with_comparator(|a, b| b.cmp(a));
+        assert_eq!(ARC_COUNTERS, reversed.heap_size_bytes());
+        reversed.insert(1);
+        assert_eq!(
+            ARC_COUNTERS + size_of::<BstNode>(),
+            reversed.heap_size_bytes()
+        );
+        let offset = 10;
+        let shifted = AvlSet::with_comparator(move |a, b| (a - offset).cmp(&(b - offset)));
+        assert_eq!(ARC_COUNTERS + size_of::<i64>(), shifted.heap_size_bytes());
+    }
+}
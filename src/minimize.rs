@@ -0,0 +1,325 @@
+use std::{
+    cell::Cell,
+    collections::{BTreeMap, BTreeSet},
+    fmt::{self, Write},
+    panic::{self, AssertUnwindSafe},
+};
+
+use crate::{
+    oplog::Entry,
+    workload::{self, Operation},
+    Tree,
+};
+
+/// What a tree under test is checked against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Oracle {
+    /// A `BTreeSet`: inserting a stored value fails.
+    Set,
+    /// A `BTreeMap` of counts: every insert adds a copy and every remove
+    /// takes one away.
+    MultiSet,
+}
+
+impl Oracle {
+    /// The oracle matching a tree that keeps duplicates or not.
+    pub fn keeping_duplicates(keeps_duplicates: bool) -> Self {
+        if keeps_duplicates {
+            Oracle::MultiSet
+        } else {
+            Oracle::Set
+        }
+    }
+
+    fn build(self) -> Box<dyn Tree> {
+        match self {
+            Oracle::Set => Box::<BTreeSet<i64>>::default(),
+            Oracle::MultiSet => Box::<BTreeMap<i64, usize>>::default(),
+        }
+    }
+}
+
+impl fmt::Display for Oracle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Oracle::Set => "BTreeSet",
+            Oracle::MultiSet => "a BTreeMap multiset",
+        })
+    }
+}
+
+/// Index of the first entry where a fresh tree from `build` answers
+/// differently from `oracle` or panics. `entries.len()` means every answer
+/// matched but the trees ended up with different values.
+pub fn first_divergence<T: Tree>(
+    oracle: Oracle,
+    build: impl Fn() -> T,
+    entries: &[Entry],
+) -> Option<usize> {
+    let step = Cell::new(0);
+    let outcome = panic::catch_unwind(AssertUnwindSafe(|| {
+        let mut tree = build();
+        let mut oracle = oracle.build();
+        for (index, entry) in entries.iter().enumerate() {
+            step.set(index);
+            let actual = workload::apply(&mut tree, entry.operation, entry.key);
+            let expected = workload::apply(oracle.as_mut(), entry.operation, entry.key);
+            if actual != expected {
+                return Some(index);
+            }
+        }
+        step.set(entries.len());
+        (tree.values() != oracle.values()).then_some(entries.len())
+    }));
+    outcome.unwrap_or_else(|_| Some(step.get()))
+}
+
+/// Shrinks a sequence on which `build` diverges from `oracle` with delta
+/// debugging: chunks of entries and their complements are dropped while the
+/// divergence stays, at ever finer granularity, until no single entry can
+/// go. Keys are then renumbered `0, 1, 2, ...` in their order if that keeps
+/// the divergence. Results are filled in from the oracle.
+///
+/// `None` when the entries do not diverge at all. Panics of the probed trees
+/// are caught, and their messages are kept quiet while this runs.
+pub fn minimize<T: Tree>(
+    oracle: Oracle,
+    build: impl Fn() -> T,
+    entries: &[Entry],
+) -> Option<Vec<Entry>> {
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let minimal = shrink(oracle, build, entries);
+    panic::set_hook(hook);
+    minimal
+}
+
+fn shrink<T: Tree>(oracle: Oracle, build: impl Fn() -> T, entries: &[Entry]) -> Option<Vec<Entry>> {
+    let fails = |candidate: &[Entry]| first_divergence(oracle, &build, candidate).is_some();
+    let first = first_divergence(oracle, &build, entries)?;
+    let mut current = entries[..entries.len().min(first + 1)].to_vec();
+    let mut granularity = 2;
+    while current.len() >= 2 {
+        let size = current.len().div_ceil(granularity);
+        let chunks = current.chunks(size).collect::<Vec<_>>();
+        let subset = chunks
+            .iter()
+            .find(|chunk| fails(chunk))
+            .map(|chunk| (chunk.to_vec(), 2));
+        let reduced = subset.or_else(|| {
+            (0..chunks.len())
+                .map(|skip| {
+                    chunks
+                        .iter()
+                        .enumerate()
+                        .filter(|(index, _)| *index != skip)
+                        .flat_map(|(_, chunk)| chunk.iter().copied())
+                        .collect::<Vec<_>>()
+                })
+                .find(|complement| fails(complement))
+                .map(|complement| (complement, (granularity - 1).max(2)))
+        });
+        match reduced {
+            Some((candidate, next)) => {
+                current = candidate;
+                granularity = next;
+            }
+            None if granularity >= current.len() => break,
+            None => granularity = (granularity * 2).min(current.len()),
+        }
+    }
+    let normalized = normalize_keys(&current);
+    if fails(&normalized) {
+        current = normalized;
+    }
+    Some(with_oracle_results(oracle, &current))
+}
+
+// Renumbers distinct keys by rank, keeping their order.
+fn normalize_keys(entries: &[Entry]) -> Vec<Entry> {
+    let keys = entries
+        .iter()
+        .map(|entry| entry.key)
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>();
+    entries
+        .iter()
+        .map(|entry| Entry {
+            key: keys.binary_search(&entry.key).unwrap() as i64,
+            ..*entry
+        })
+        .collect()
+}
+
+fn with_oracle_results(oracle: Oracle, entries: &[Entry]) -> Vec<Entry> {
+    let mut oracle = oracle.build();
+    entries
+        .iter()
+        .map(|entry| Entry {
+            result: Some(workload::apply(oracle.as_mut(), entry.operation, entry.key)),
+            ..*entry
+        })
+        .collect()
+}
+
+/// A `#[test]` named `name` that runs `entries` on `type_name::default()`
+/// and asserts what `oracle` answers, ending with its values.
+pub fn to_test(oracle: Oracle, name: &str, type_name: &str, entries: &[Entry]) -> String {
+    let mutates = entries
+        .iter()
+        .any(|entry| entry.operation != Operation::Search);
+    let mut test = format!(
+        "#[test]\nfn {name}() {{\n    let {}tree = {type_name}::default();\n",
+        if mutates { "mut " } else { "" }
+    );
+    let mut oracle = oracle.build();
+    for entry in entries {
+        let key = entry.key;
+        let expected = workload::apply(oracle.as_mut(), entry.operation, key);
+        let not = if expected { "" } else { "!" };
+        let line = match entry.operation {
+            Operation::Insert => format!("assert!({not}tree.insert({key}));"),
+            Operation::Remove if expected => {
+                format!("assert_eq!(Some({key}), tree.remove({key}));")
+            }
+            Operation::Remove => format!("assert!(tree.remove({key}).is_none());"),
+            Operation::Search => format!("assert!({not}tree.search({key}));"),
+        };
+        writeln!(test, "    {line}").unwrap();
+    }
+    let values = oracle.values();
+    if values.is_empty() {
+        test.push_str("    assert!(tree.values().is_empty());\n");
+    } else {
+        let values = values.iter().map(i64::to_string).collect::<Vec<_>>();
+        writeln!(
+            test,
+            "    assert_eq!(vec![{}], tree.values());",
+            values.join(", ")
+        )
+        .unwrap();
+    }
+    test.push_str("}\n");
+    test
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use crate::{avl::AvlSet, multiset::AvlMultiSet};
+
+    use super::*;
+
+    // Refuses to remove its largest value once it holds three or more.
+    #[derive(Default)]
+    struct KeepsMax(BTreeSet<i64>);
+
+    impl Tree for KeepsMax {
+        fn insert(&mut self, value: i64) -> bool {
+            self.0.insert(value)
+        }
+
+        fn remove(&mut self, value: i64) -> Option<i64> {
+            if self.0.len() >= 3 && self.0.last() == Some(&value) {
+                return None;
+            }
+            self.0.take(&value)
+        }
+
+        fn search(&self, value: i64) -> bool {
+            self.0.contains(&value)
+        }
+
+        fn values(&self) -> Vec<i64> {
+            self.0.iter().copied().collect()
+        }
+    }
+
+    fn random_entries(n: usize) -> Vec<Entry> {
+        let mut rng = StdRng::seed_from_u64(3);
+        (0..n)
+            .map(|_| Entry {
+                operation: [Operation::Insert, Operation::Insert, Operation::Remove]
+                    [rng.gen_range(0..3)],
+                key: rng.gen_range(-10..10),
+                result: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_minimize() {
+        let entries = random_entries(2000);
+        assert!(first_divergence(Oracle::Set, AvlSet::default, &entries).is_none());
+        assert!(minimize(Oracle::Set, AvlSet::default, &entries).is_none());
+        assert!(first_divergence(Oracle::Set, KeepsMax::default, &entries).is_some());
+
+        let minimal = minimize(Oracle::Set, KeepsMax::default, &entries).unwrap();
+        assert_eq!(4, minimal.len());
+        let mut inserted = minimal[..3]
+            .iter()
+            .inspect(|entry| assert_eq!(Operation::Insert, entry.operation))
+            .map(|entry| entry.key)
+            .collect::<Vec<_>>();
+        inserted.sort_unstable();
+        assert_eq!(vec![0, 1, 2], inserted);
+        assert_eq!(
+            Entry {
+                operation: Operation::Remove,
+                key: 2,
+                result: Some(true)
+            },
+            minimal[3]
+        );
+    }
+
+    #[test]
+    fn test_multiset_oracle() {
+        let entries = random_entries(2000);
+        // Duplicates are what a multiset is for, not a divergence.
+        assert!(first_divergence(Oracle::Set, AvlMultiSet::default, &entries).is_some());
+        assert!(first_divergence(Oracle::MultiSet, AvlMultiSet::default, &entries).is_none());
+        let minimal = minimize(Oracle::MultiSet, AvlSet::default, &entries).unwrap();
+        assert_eq!(
+            vec![Operation::Insert, Operation::Insert],
+            minimal
+                .iter()
+                .map(|entry| entry.operation)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(minimal[0].key, minimal[1].key);
+        assert!(to_test(Oracle::MultiSet, "test_duplicates", "AvlSet", &minimal)
+            .contains("    assert!(tree.insert(0));\n    assert!(tree.insert(0));\n    assert_eq!(vec![0, 0], tree.values());"));
+    }
+
+    #[test]
+    fn test_panics_diverge() {
+        let entries = random_entries(10);
+        let panicking = || -> Box<dyn Tree> { panic!("cannot build") };
+        assert_eq!(Some(0), first_divergence(Oracle::Set, panicking, &entries));
+        assert_eq!(1, minimize(Oracle::Set, panicking, &entries).unwrap().len());
+    }
+
+    #[test]
+    fn test_to_test() {
+        let entries = crate::oplog::parse("I 1\nI 0\nS 1\nR 1\nR 5").unwrap();
+        assert_eq!(
+            "#[test]
+fn test_keeps_max() {
+    let mut tree = BstSet::default();
+    assert!(tree.insert(1));
+    assert!(tree.insert(0));
+    assert!(tree.search(1));
+    assert_eq!(Some(1), tree.remove(1));
+    assert!(tree.remove(5).is_none());
+    assert_eq!(vec![0], tree.values());
+}
+",
+            to_test(Oracle::Set, "test_keeps_max", "BstSet", &entries)
+        );
+        assert!(to_test(Oracle::Set, "test_empty", "AvlSet", &entries[2..3])
+            .contains("    let tree = AvlSet::default();\n    assert!(!tree.search(1));\n    assert!(tree.values().is_empty());"));
+    }
+}
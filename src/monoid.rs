@@ -0,0 +1,131 @@
+use std::{
+    cmp::{max, min, Ordering},
+    fmt::Debug,
+    ops::Bound,
+};
+
+use crate::comparator::Comparator;
+
+/// Summary of a subtree that trees keep up to date in every node.
+///
+/// `combine` must be associative and `identity` must be its neutral element.
+/// Summaries are combined in key order, so the operation need not be commutative.
+/// Sets summarize their `i64` keys; other trees built on the same nodes pick
+/// their own key type `K`.
+pub trait Monoid<K = i64> {
+    type Summary: Debug + Clone + PartialEq;
+
+    fn identity() -> Self::Summary;
+    fn lift(value: K) -> Self::Summary;
+    fn combine(left: &Self::Summary, right: &Self::Summary) -> Self::Summary;
+}
+
+/// No augmentation: plain sets carry a zero-sized summary.
+impl Monoid for () {
+    type Summary = ();
+
+    fn identity() -> Self::Summary {}
+
+    fn lift(_value: i64) -> Self::Summary {}
+
+    fn combine(_left: &Self::Summary, _right: &Self::Summary) -> Self::Summary {}
+}
+
+/// Number of keys. Folding `..x` gives the rank of `x`.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Count;
+
+impl Monoid for Count {
+    type Summary = usize;
+
+    fn identity() -> Self::Summary {
+        0
+    }
+
+    fn lift(_value: i64) -> Self::Summary {
+        1
+    }
+
+    fn combine(left: &Self::Summary, right: &Self::Summary) -> Self::Summary {
+        left + right
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Sum;
+
+impl Monoid for Sum {
+    type Summary = i64;
+
+    fn identity() -> Self::Summary {
+        0
+    }
+
+    fn lift(value: i64) -> Self::Summary {
+        value
+    }
+
+    fn combine(left: &Self::Summary, right: &Self::Summary) -> Self::Summary {
+        left + right
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Min;
+
+impl Monoid for Min {
+    type Summary = Option<i64>;
+
+    fn identity() -> Self::Summary {
+        None
+    }
+
+    fn lift(value: i64) -> Self::Summary {
+        Some(value)
+    }
+
+    fn combine(left: &Self::Summary, right: &Self::Summary) -> Self::Summary {
+        match (left, right) {
+            (None, x) | (x, None) => *x,
+            (Some(l), Some(r)) => Some(min(*l, *r)),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Max;
+
+impl Monoid for Max {
+    type Summary = Option<i64>;
+
+    fn identity() -> Self::Summary {
+        None
+    }
+
+    fn lift(value: i64) -> Self::Summary {
+        Some(value)
+    }
+
+    fn combine(left: &Self::Summary, right: &Self::Summary) -> Self::Summary {
+        match (left, right) {
+            (None, x) | (x, None) => *x,
+            (Some(l), Some(r)) => Some(max(*l, *r)),
+        }
+    }
+}
+
+pub(crate) fn after_start(cmp: &Comparator, start: Bound<&i64>, value: i64) -> bool {
+    match start {
+        Bound::Included(start) => cmp.compare(start, &value) != Ordering::Greater,
+        Bound::Excluded(start) => cmp.compare(start, &value) == Ordering::Less,
+        Bound::Unbounded => true,
+    }
+}
+
+pub(crate) fn before_end(cmp: &Comparator, end: Bound<&i64>, value: i64) -> bool {
+    match end {
+        Bound::Included(end) => cmp.compare(&value, end) != Ordering::Greater,
+        Bound::Excluded(end) => cmp.compare(&value, end) == Ordering::Less,
+        Bound::Unbounded => true,
+    }
+}
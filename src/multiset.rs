@@ -0,0 +1,528 @@
+use std::cmp::Ordering;
+
+use crate::{
+    avl::{self, AvlNode, AvlTree},
+    link::Owned,
+    memory::HeapSize,
+    monoid::{Count, Monoid},
+    treap::{self, Node as TreapNode, Treap},
+    viz::{priority_label, Shape},
+    Tree,
+};
+
+// A distinct value with its multiplicity. Both trees below keep one per node
+// and order them by `value` alone.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Counted {
+    value: i64,
+    count: usize,
+}
+
+impl Counted {
+    fn new(value: i64) -> Self {
+        Self { value, count: 1 }
+    }
+}
+
+fn by_value(a: &Counted, b: &Counted) -> Ordering {
+    a.value.cmp(&b.value)
+}
+
+/// Counts every copy, so the summary of a subtree is its total multiplicity.
+impl Monoid<Counted> for Count {
+    type Summary = usize;
+
+    fn identity() -> Self::Summary {
+        0
+    }
+
+    fn lift(value: Counted) -> Self::Summary {
+        value.count
+    }
+
+    fn combine(left: &Self::Summary, right: &Self::Summary) -> Self::Summary {
+        left + right
+    }
+}
+
+// What counting, rank/select and iteration need from a node of either tree.
+trait MultiNode {
+    fn counted(&self) -> Counted;
+    fn left(&self) -> Option<&Self>;
+    fn right(&self) -> Option<&Self>;
+    /// Total multiplicity of the subtree.
+    fn size(&self) -> usize;
+}
+
+impl MultiNode for AvlNode<Count, Counted> {
+    fn counted(&self) -> Counted {
+        self.value
+    }
+
+    fn left(&self) -> Option<&Self> {
+        self.left.as_deref()
+    }
+
+    fn right(&self) -> Option<&Self> {
+        self.right.as_deref()
+    }
+
+    fn size(&self) -> usize {
+        self.summary
+    }
+}
+
+impl MultiNode for TreapNode<Count, Counted> {
+    fn counted(&self) -> Counted {
+        self.value
+    }
+
+    fn left(&self) -> Option<&Self> {
+        self.left.as_deref()
+    }
+
+    fn right(&self) -> Option<&Self> {
+        self.right.as_deref()
+    }
+
+    fn size(&self) -> usize {
+        self.summary
+    }
+}
+
+fn size<N: MultiNode>(node: Option<&N>) -> usize {
+    node.map_or(0, N::size)
+}
+
+fn count<N: MultiNode>(mut current: Option<&N>, value: i64) -> usize {
+    while let Some(node) = current {
+        current = match node.counted().value.cmp(&value) {
+            Ordering::Less => node.right(),
+            Ordering::Equal => return node.counted().count,
+            Ordering::Greater => node.left(),
+        }
+    }
+    0
+}
+
+fn rank<N: MultiNode>(mut current: Option<&N>, value: i64) -> usize {
+    let mut rank = 0;
+    while let Some(node) = current {
+        current = match node.counted().value.cmp(&value) {
+            Ordering::Less => {
+                rank += size(node.left()) + node.counted().count;
+                node.right()
+            }
+            Ordering::Equal => return rank + size(node.left()),
+            Ordering::Greater => node.left(),
+        }
+    }
+    rank
+}
+
+fn select<N: MultiNode>(mut current: Option<&N>, index: usize) -> Option<i64> {
+    let mut index = index;
+    while let Some(node) = current {
+        let left_size = size(node.left());
+        let Counted { value, count } = node.counted();
+        if index < left_size {
+            current = node.left();
+        } else if index < left_size + count {
+            return Some(value);
+        } else {
+            index -= left_size + count;
+            current = node.right();
+        }
+    }
+    None
+}
+
+fn nodes<N: MultiNode>(node: Option<&N>) -> usize {
+    node.map_or(0, |node| 1 + nodes(node.left()) + nodes(node.right()))
+}
+
+// `3x2` for a value stored twice.
+fn count_label(value: i64, count: usize) -> String {
+    if count > 1 {
+        format!("{value}x{count}")
+    } else {
+        value.to_string()
+    }
+}
+
+type AvlMultiTree = AvlTree<Count, Counted>;
+
+/// AVL tree that keeps duplicates as a per-node count.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct AvlMultiSet {
+    root: AvlMultiTree,
+}
+
+impl AvlMultiSet {
+    /// Total number of elements, counting duplicates.
+    pub fn len(&self) -> usize {
+        size(self.root.as_deref())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    pub fn remove_one(&mut self, value: i64) -> bool {
+        self.remove_n(value, 1) > 0
+    }
+
+    /// Returns how many copies were removed.
+    pub fn remove_all(&mut self, value: i64) -> usize {
+        self.remove_n(value, usize::MAX)
+    }
+
+    pub fn count(&self, value: i64) -> usize {
+        count(self.root.as_deref(), value)
+    }
+
+    /// Number of elements strictly less than `value`.
+    pub fn rank(&self, value: i64) -> usize {
+        rank(self.root.as_deref(), value)
+    }
+
+    /// The element at position `index` in sorted order, starting from zero.
+    pub fn select(&self, index: usize) -> Option<i64> {
+        select(self.root.as_deref(), index)
+    }
+
+    pub fn iter(&self) -> AvlMultiIter<'_> {
+        AvlMultiIter(Repeated::new(self.root.as_deref()))
+    }
+
+    fn remove_n(&mut self, value: i64, n: usize) -> usize {
+        let stored = self.count(value);
+        let key = Counted::new(value);
+        if stored > n {
+            avl::update_by::<Count, Counted, Owned>(&mut self.root, &key, &by_value, |counted| {
+                counted.count -= n
+            });
+            n
+        } else {
+            avl::remove_by::<Count, Counted, Owned>(&mut self.root, key, &by_value);
+            stored
+        }
+    }
+}
+
+fn avl_shape(tree: &AvlMultiTree) -> Option<Shape> {
+    tree.as_ref().map(|node| {
+        Shape::new(
+            node.value.value,
+            count_label(node.value.value, node.value.count),
+            avl_shape(&node.left),
+            avl_shape(&node.right),
+        )
+    })
+}
+
+impl Tree for AvlMultiSet {
+    fn insert(&mut self, value: i64) -> bool {
+        let key = Counted::new(value);
+        if !avl::update_by::<Count, Counted, Owned>(&mut self.root, &key, &by_value, |counted| {
+            counted.count += 1
+        }) {
+            avl::insert_by::<Count, Counted, Owned>(&mut self.root, key, &by_value);
+        }
+        true
+    }
+
+    fn remove(&mut self, value: i64) -> Option<i64> {
+        self.remove_one(value).then_some(value)
+    }
+
+    fn search(&self, value: i64) -> bool {
+        self.count(value) > 0
+    }
+
+    fn values(&self) -> Vec<i64> {
+        self.iter().copied().collect()
+    }
+
+    fn shape(&self) -> Option<Shape> {
+        avl_shape(&self.root)
+    }
+}
+
+// In-order walk that yields every value `count` times.
+#[derive(Debug)]
+struct Repeated<'a, N> {
+    prev_nodes: Vec<&'a N>,
+    current: Option<&'a N>,
+    repeat: Option<(&'a N, usize)>,
+}
+
+impl<'a, N: MultiNode> Repeated<'a, N> {
+    fn new(root: Option<&'a N>) -> Self {
+        Self {
+            prev_nodes: vec![],
+            current: root,
+            repeat: None,
+        }
+    }
+
+    fn next(&mut self) -> Option<&'a N> {
+        if let Some((node, left)) = self.repeat.as_mut() {
+            if *left > 0 {
+                *left -= 1;
+                return Some(node);
+            }
+        }
+        while let Some(node) = self.current {
+            self.prev_nodes.push(node);
+            self.current = node.left();
+        }
+        let node = self.prev_nodes.pop()?;
+        self.current = node.right();
+        self.repeat = Some((node, node.counted().count - 1));
+        Some(node)
+    }
+}
+
+#[derive(Debug)]
+pub struct AvlMultiIter<'a>(Repeated<'a, AvlNode<Count, Counted>>);
+
+impl<'a> Iterator for AvlMultiIter<'a> {
+    type Item = &'a i64;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|node| &node.value.value)
+    }
+}
+
+type MultiTreap = Treap<Count, Counted>;
+
+// Cuts the treap into values below `value`, the node holding `value` (if
+// any) and values above it.
+fn split_out(t: MultiTreap, value: i64) -> (MultiTreap, MultiTreap, MultiTreap) {
+    let (less, rest) = treap::split_by::<Count, Counted, Owned>(t, &|key| key.value < value);
+    let (equal, greater) =
+        treap::split_by::<Count, Counted, Owned>(rest, &|key| key.value <= value);
+    (less, equal, greater)
+}
+
+fn merge_back(less: MultiTreap, equal: MultiTreap, greater: MultiTreap) -> MultiTreap {
+    let less = treap::merge::<Count, Counted, Owned>(less, equal);
+    treap::merge::<Count, Counted, Owned>(less, greater)
+}
+
+/// Treap that keeps duplicates as a per-node count.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct TreapMultiSet {
+    root: MultiTreap,
+}
+
+impl TreapMultiSet {
+    /// Total number of elements, counting duplicates.
+    pub fn len(&self) -> usize {
+        size(self.root.as_deref())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    pub fn remove_one(&mut self, value: i64) -> bool {
+        self.remove_n(value, 1) > 0
+    }
+
+    /// Returns how many copies were removed.
+    pub fn remove_all(&mut self, value: i64) -> usize {
+        self.remove_n(value, usize::MAX)
+    }
+
+    pub fn count(&self, value: i64) -> usize {
+        count(self.root.as_deref(), value)
+    }
+
+    /// Number of elements strictly less than `value`.
+    pub fn rank(&self, value: i64) -> usize {
+        rank(self.root.as_deref(), value)
+    }
+
+    /// The element at position `index` in sorted order, starting from zero.
+    pub fn select(&self, index: usize) -> Option<i64> {
+        select(self.root.as_deref(), index)
+    }
+
+    pub fn iter(&self) -> TreapMultiIter<'_> {
+        TreapMultiIter(Repeated::new(self.root.as_deref()))
+    }
+
+    fn remove_n(&mut self, value: i64, n: usize) -> usize {
+        let (less, equal, greater) = split_out(self.root.take(), value);
+        let (equal, removed) = match equal {
+            None => (None, 0),
+            Some(mut node) if node.value.count > n => {
+                node.value.count -= n;
+                node.update_summary();
+                (Some(node), n)
+            }
+            Some(node) => (None, node.value.count),
+        };
+        self.root = merge_back(less, equal, greater);
+        removed
+    }
+}
+
+fn treap_shape(tree: &MultiTreap) -> Option<Shape> {
+    tree.as_ref().map(|node| {
+        Shape::new(
+            node.value.value,
+            priority_label(
+                count_label(node.value.value, node.value.count),
+                node.priority,
+            ),
+            treap_shape(&node.left),
+            treap_shape(&node.right),
+        )
+    })
+}
+
+impl Tree for TreapMultiSet {
+    fn insert(&mut self, value: i64) -> bool {
+        let (less, equal, greater) = split_out(self.root.take(), value);
+        let equal = match equal {
+            Some(mut node) => {
+                node.value.count += 1;
+                node.update_summary();
+                node
+            }
+            None => Box::new(TreapNode::new(Counted::new(value))),
+        };
+        self.root = merge_back(less, Some(equal), greater);
+        true
+    }
+
+    fn remove(&mut self, value: i64) -> Option<i64> {
+        self.remove_one(value).then_some(value)
+    }
+
+    fn search(&self, value: i64) -> bool {
+        self.count(value) > 0
+    }
+
+    fn values(&self) -> Vec<i64> {
+        self.iter().copied().collect()
+    }
+
+    fn shape(&self) -> Option<Shape> {
+        treap_shape(&self.root)
+    }
+}
+
+#[derive(Debug)]
+pub struct TreapMultiIter<'a>(Repeated<'a, TreapNode<Count, Counted>>);
+
+impl<'a> Iterator for TreapMultiIter<'a> {
+    type Item = &'a i64;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|node| &node.value.value)
+    }
+}
+
+// Duplicates only bump a counter, so the size depends on distinct values.
+impl HeapSize for AvlMultiSet {
+    fn heap_size_bytes(&self) -> usize {
+        nodes(self.root.as_deref()) * size_of::<AvlNode<Count, Counted>>()
+    }
+}
+
+impl HeapSize for TreapMultiSet {
+    fn heap_size_bytes(&self) -> usize {
+        nodes(self.root.as_deref()) * size_of::<TreapNode<Count, Counted>>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use rand::Rng;
+
+    use super::*;
+
+    #[test]
+    fn test_avl_multiset() {
+        let mut tree = AvlMultiSet::default();
+        for value in [5, 3, 5, 8, 3, 5, 1] {
+            tree.insert(value);
+        }
+        assert_eq!(
+            vec![1, 3, 3, 5, 5, 5, 8],
+            tree.iter().copied().collect::<Vec<_>>()
+        );
+        assert_eq!(3, tree.count(5));
+        assert_eq!(3, tree.rank(5));
+        assert_eq!(Some(5), tree.select(5));
+        assert_eq!(None, tree.select(7));
+        assert!(tree.remove_one(5));
+        assert_eq!(2, tree.remove_all(3));
+        assert_eq!(0, tree.remove_all(3));
+        assert_eq!(vec![1, 5, 5, 8], tree.iter().copied().collect::<Vec<_>>());
+        assert_eq!(4, tree.len());
+    }
+
+    #[test]
+    fn test_treap_multiset() {
+        let mut tree = TreapMultiSet::default();
+        for value in [5, 3, 5, 8, 3, 5, 1] {
+            tree.insert(value);
+        }
+        assert_eq!(
+            vec![1, 3, 3, 5, 5, 5, 8],
+            tree.iter().copied().collect::<Vec<_>>()
+        );
+        assert_eq!(2, tree.count(3));
+        assert_eq!(6, tree.rank(8));
+        assert_eq!(Some(3), tree.select(2));
+        assert_eq!(Some(5), tree.remove(5));
+        assert_eq!(None, tree.remove(4));
+        assert_eq!(2, tree.remove_all(5));
+        assert_eq!(vec![1, 3, 3, 8], tree.iter().copied().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_against_btreemap() {
+        let mut rng = rand::thread_rng();
+        let mut avl = AvlMultiSet::default();
+        let mut treap = TreapMultiSet::default();
+        let mut expected = BTreeMap::<i64, usize>::new();
+        for _ in 0..2000 {
+            let value = rng.gen_range(0..50);
+            if rng.gen_bool(0.6) {
+                avl.insert(value);
+                treap.insert(value);
+                *expected.entry(value).or_default() += 1;
+            } else {
+                let n = rng.gen_range(1..3);
+                let stored = expected.entry(value).or_default();
+                let removed = n.min(*stored);
+                *stored -= removed;
+                if *stored == 0 {
+                    expected.remove(&value);
+                }
+                assert_eq!(removed, avl.remove_n(value, n));
+                assert_eq!(removed, treap.remove_n(value, n));
+            }
+        }
+        let values = expected
+            .iter()
+            .flat_map(|(&value, &count)| std::iter::repeat_n(value, count))
+            .collect::<Vec<_>>();
+        assert_eq!(values, avl.values());
+        assert_eq!(values, treap.values());
+        assert_eq!(values.len(), avl.len());
+        assert_eq!(
+            Some(values[values.len() / 2]),
+            treap.select(values.len() / 2)
+        );
+        assert!(avl.root.as_ref().unwrap().height <= 9);
+    }
+}
@@ -0,0 +1,348 @@
+//! Plain-text logs of tree operations, one per line:
+//!
+//! ```text
+//! # comments and blank lines are skipped
+//! I 42 1
+//! R 17 0
+//! S 5
+//! ```
+//!
+//! `I`, `R` and `S` stand for insert, remove and search. The optional last
+//! field is what the operation returned when it was recorded: `1` for an
+//! insert or remove that changed the tree and for a search that found the key.
+
+use std::{
+    cell::RefCell,
+    fmt, fs,
+    io::{self, ErrorKind},
+    path::Path,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    histogram::LatencyHistogram,
+    viz::Shape,
+    workload::{self, Operation},
+    Tree,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Entry {
+    pub operation: Operation,
+    pub key: i64,
+    pub result: Option<bool>,
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let letter = match self.operation {
+            Operation::Insert => 'I',
+            Operation::Remove => 'R',
+            Operation::Search => 'S',
+        };
+        write!(f, "{letter} {}", self.key)?;
+        match self.result {
+            Some(result) => write!(f, " {}", u8::from(result)),
+            None => Ok(()),
+        }
+    }
+}
+
+impl FromStr for Entry {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields = s.split_whitespace().collect::<Vec<_>>();
+        let (operation, key, result) = match fields.as_slice() {
+            [operation, key] => (operation, key, None),
+            [operation, key, result] => (operation, key, Some(result)),
+            _ => return Err(format!("expected `<I|R|S> <key> [0|1]`, got {s:?}")),
+        };
+        let operation = match *operation {
+            "I" => Operation::Insert,
+            "R" => Operation::Remove,
+            "S" => Operation::Search,
+            operation => return Err(format!("unknown operation {operation:?}")),
+        };
+        let key = key
+            .parse()
+            .map_err(|_| format!("cannot parse key {key:?}"))?;
+        let result = match result.copied() {
+            None => None,
+            Some("0") => Some(false),
+            Some("1") => Some(true),
+            Some(result) => return Err(format!("result must be 0 or 1, got {result:?}")),
+        };
+        Ok(Self {
+            operation,
+            key,
+            result,
+        })
+    }
+}
+
+pub fn to_text(entries: &[Entry]) -> String {
+    let mut text = String::new();
+    for entry in entries {
+        text.push_str(&entry.to_string());
+        text.push('\n');
+    }
+    text
+}
+
+/// Parses a log. Errors name the line they come from.
+pub fn parse(text: &str) -> Result<Vec<Entry>, String> {
+    text.lines()
+        .enumerate()
+        .map(|(index, line)| (index, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(index, line)| {
+            line.parse()
+                .map_err(|error| format!("line {}: {error}", index + 1))
+        })
+        .collect()
+}
+
+pub fn write_log(path: impl AsRef<Path>, entries: &[Entry]) -> io::Result<()> {
+    fs::write(path, to_text(entries))
+}
+
+pub fn read_log(path: impl AsRef<Path>) -> io::Result<Vec<Entry>> {
+    parse(&fs::read_to_string(path)?).map_err(|error| io::Error::new(ErrorKind::InvalidData, error))
+}
+
+/// Wraps a tree and logs every operation made through it, with its result.
+/// Searches only borrow the tree, so the log sits in a `RefCell`.
+#[derive(Debug, Default, Clone)]
+pub struct Recorder<T> {
+    tree: T,
+    entries: RefCell<Vec<Entry>>,
+}
+
+impl<T: Tree> Recorder<T> {
+    pub fn new(tree: T) -> Self {
+        Self {
+            tree,
+            entries: RefCell::default(),
+        }
+    }
+
+    pub fn entries(&self) -> Vec<Entry> {
+        self.entries.borrow().clone()
+    }
+
+    pub fn into_parts(self) -> (T, Vec<Entry>) {
+        (self.tree, self.entries.into_inner())
+    }
+
+    fn record(&self, operation: Operation, key: i64, result: bool) -> bool {
+        self.entries.borrow_mut().push(Entry {
+            operation,
+            key,
+            result: Some(result),
+        });
+        result
+    }
+}
+
+impl<T: Tree> Tree for Recorder<T> {
+    fn insert(&mut self, value: i64) -> bool {
+        let inserted = self.tree.insert(value);
+        self.record(Operation::Insert, value, inserted)
+    }
+
+    fn remove(&mut self, value: i64) -> Option<i64> {
+        let removed = self.tree.remove(value);
+        self.record(Operation::Remove, value, removed.is_some());
+        removed
+    }
+
+    fn search(&self, value: i64) -> bool {
+        self.record(Operation::Search, value, self.tree.search(value))
+    }
+
+    fn values(&self) -> Vec<i64> {
+        self.tree.values()
+    }
+
+    fn shape(&self) -> Option<Shape> {
+        self.tree.shape()
+    }
+}
+
+/// An entry whose result on replay differs from the recorded one, or a
+/// remove that handed back another key than the one asked for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mismatch {
+    /// Position in the replayed entries, starting at zero.
+    pub index: usize,
+    pub entry: Entry,
+    pub actual: bool,
+    /// What a remove returned, if it was not the requested key.
+    pub removed: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayResult {
+    pub operations: usize,
+    pub elapsed: Duration,
+    pub latency: LatencyHistogram,
+    pub mismatches: Vec<Mismatch>,
+}
+
+impl ReplayResult {
+    /// Zero for an empty log.
+    pub fn ops_per_sec(&self) -> f64 {
+        match self.elapsed.as_secs_f64() {
+            seconds if seconds > 0.0 => self.operations as f64 / seconds,
+            _ => 0.0,
+        }
+    }
+}
+
+/// Runs every entry against `tree`, timing each operation and comparing
+/// results with the recorded ones where the log has them.
+pub fn replay(tree: &mut dyn Tree, entries: &[Entry]) -> ReplayResult {
+    let mut latency = LatencyHistogram::default();
+    let mut mismatches = vec![];
+    let start = Instant::now();
+    for (index, entry) in entries.iter().enumerate() {
+        let op_start = Instant::now();
+        // Removes are checked here rather than in `workload::apply`, which
+        // panics on a wrong key: a broken tree is what replays look for.
+        let (actual, removed) = match entry.operation {
+            Operation::Remove => {
+                let removed = tree.remove(entry.key);
+                (
+                    removed.is_some(),
+                    removed.filter(|removed| *removed != entry.key),
+                )
+            }
+            operation => (workload::apply(tree, operation, entry.key), None),
+        };
+        latency.record(op_start.elapsed());
+        if removed.is_some() || entry.result.is_some_and(|expected| expected != actual) {
+            mismatches.push(Mismatch {
+                index,
+                entry: *entry,
+                actual,
+                removed,
+            });
+        }
+    }
+    ReplayResult {
+        operations: entries.len(),
+        elapsed: start.elapsed(),
+        latency,
+        mismatches,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeSet, env, process};
+
+    use crate::{avl::AvlSet, bst::BstSet, multiset::AvlMultiSet, workload::Workload};
+
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        let entries = parse("# header\nI 42 1\n\n  R -17 0\nS 5\n").unwrap();
+        assert_eq!(
+            vec![
+                Entry {
+                    operation: Operation::Insert,
+                    key: 42,
+                    result: Some(true)
+                },
+                Entry {
+                    operation: Operation::Remove,
+                    key: -17,
+                    result: Some(false)
+                },
+                Entry {
+                    operation: Operation::Search,
+                    key: 5,
+                    result: None
+                },
+            ],
+            entries
+        );
+        assert_eq!("I 42 1\nR -17 0\nS 5\n", to_text(&entries));
+        assert_eq!(
+            Err("line 2: unknown operation \"X\"".to_string()),
+            parse("I 1\nX 1")
+        );
+        assert!(parse("I 1 2").is_err());
+        assert!(parse("I").is_err());
+
+        let path = env::temp_dir().join(format!("oplog_round_trip_{}.log", process::id()));
+        write_log(&path, &entries).unwrap();
+        let read = read_log(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(entries, read);
+    }
+
+    #[test]
+    fn test_record_and_replay() {
+        let mut recorder = Recorder::new(AvlSet::default());
+        workload::run(&mut recorder, &Workload::random(500));
+        let (tree, entries) = recorder.into_parts();
+        assert_eq!(500 + 50 + 50, entries.len());
+
+        let mut bst = BstSet::default();
+        let result = replay(&mut bst, &entries);
+        assert_eq!(entries.len(), result.operations);
+        assert_eq!(entries.len() as u64, result.latency.len());
+        assert!(result.mismatches.is_empty());
+        assert_eq!(tree.values(), bst.values());
+
+        // A multiset accepts duplicates the set refused.
+        let result = replay(&mut AvlMultiSet::default(), &entries);
+        let duplicate = entries
+            .iter()
+            .position(|entry| entry.operation == Operation::Insert && entry.result == Some(false))
+            .unwrap();
+        assert_eq!(duplicate, result.mismatches[0].index);
+        assert!(result.mismatches[0].actual);
+    }
+
+    // Hands back the next key on every remove.
+    #[derive(Default)]
+    struct WrongKey(BTreeSet<i64>);
+
+    impl Tree for WrongKey {
+        fn insert(&mut self, value: i64) -> bool {
+            self.0.insert(value)
+        }
+
+        fn remove(&mut self, value: i64) -> Option<i64> {
+            self.0.remove(&value).then_some(value + 1)
+        }
+
+        fn search(&self, value: i64) -> bool {
+            self.0.contains(&value)
+        }
+
+        fn values(&self) -> Vec<i64> {
+            self.0.iter().copied().collect()
+        }
+    }
+
+    #[test]
+    fn test_replay_wrong_removed_key() {
+        let entries = parse("I 1 1\nR 1 1\nR 1 0").unwrap();
+        let result = replay(&mut WrongKey::default(), &entries);
+        assert_eq!(
+            vec![Mismatch {
+                index: 1,
+                entry: entries[1],
+                actual: true,
+                removed: Some(2),
+            }],
+            result.mismatches
+        );
+    }
+}
@@ -0,0 +1,48 @@
+use rayon::slice::ParallelSliceMut;
+
+use crate::comparator::Comparator;
+
+// Below this depth the recursion has already produced more tasks than any
+// pool has threads, and forking further only costs time.
+const FORK_DEPTH: usize = 12;
+
+/// Runs `a` and `b` in parallel near the top of a recursion and one after the
+/// other deeper down.
+pub(crate) fn join<A, B, RA, RB>(depth: usize, a: A, b: B) -> (RA, RB)
+where
+    A: FnOnce() -> RA + Send,
+    B: FnOnce() -> RB + Send,
+    RA: Send,
+    RB: Send,
+{
+    if depth < FORK_DEPTH {
+        rayon::join(a, b)
+    } else {
+        (a(), b())
+    }
+}
+
+/// Sorts `values` on the rayon pool and drops duplicates.
+pub(crate) fn sorted_unique(cmp: &Comparator, mut values: Vec<i64>) -> Vec<i64> {
+    values.par_sort_unstable_by(|a, b| cmp.compare(a, b));
+    values.dedup_by(|a, b| cmp.compare(a, b).is_eq());
+    values
+}
+
+/// `build_balanced` with both halves laid out in parallel.
+pub(crate) fn build_balanced<T: Send>(
+    depth: usize,
+    sorted: &[i64],
+    node: &(impl Fn(i64, Option<T>, Option<T>) -> T + Sync),
+) -> Option<T> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let middle = sorted.len() / 2;
+    let (left, right) = join(
+        depth,
+        || build_balanced(depth + 1, &sorted[..middle], node),
+        || build_balanced(depth + 1, &sorted[middle + 1..], node),
+    );
+    Some(node(sorted[middle], left, right))
+}
@@ -0,0 +1,292 @@
+use std::cmp::Ordering;
+
+use crate::{
+    avl::{self, AvlNode, AvlTree},
+    comparator::Comparator,
+    link::Shared,
+    memory::{HeapSize, ARC_COUNTERS},
+    treap::{self, Node as TreapNode, Treap},
+    viz::Shape,
+    Tree,
+};
+
+// Persistent sets always use the natural order.
+const NATURAL: Comparator = Comparator::natural();
+
+type PersistentTreap = Treap<(), i64, Shared>;
+
+/// Treap whose versions share unchanged subtrees. Cloning is O(1).
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct PersistentTreapSet {
+    root: PersistentTreap,
+}
+
+impl PersistentTreapSet {
+    pub fn inserted(&self, value: i64) -> Self {
+        if self.search(value) {
+            return self.clone();
+        }
+        Self {
+            root: treap::insert::<(), Shared>(&NATURAL, value, self.root.clone()),
+        }
+    }
+
+    pub fn removed(&self, value: i64) -> Self {
+        if !self.search(value) {
+            return self.clone();
+        }
+        Self {
+            root: treap::remove::<(), Shared>(&NATURAL, value, self.root.clone()),
+        }
+    }
+
+    pub fn iter(&self) -> PersistentTreapIter<'_> {
+        PersistentTreapIter {
+            prev_nodes: vec![],
+            current: &self.root,
+        }
+    }
+}
+
+impl Tree for PersistentTreapSet {
+    fn insert(&mut self, value: i64) -> bool {
+        if self.search(value) {
+            return false;
+        }
+        self.root = treap::insert::<(), Shared>(&NATURAL, value, self.root.take());
+        true
+    }
+
+    fn remove(&mut self, value: i64) -> Option<i64> {
+        if !self.search(value) {
+            return None;
+        }
+        self.root = treap::remove::<(), Shared>(&NATURAL, value, self.root.take());
+        Some(value)
+    }
+
+    fn search(&self, value: i64) -> bool {
+        let mut current = &self.root;
+        while let Some(node) = current {
+            match node.value.cmp(&value) {
+                Ordering::Less => current = &node.right,
+                Ordering::Equal => return true,
+                Ordering::Greater => current = &node.left,
+            }
+        }
+        false
+    }
+
+    fn values(&self) -> Vec<i64> {
+        self.iter().copied().collect()
+    }
+
+    fn shape(&self) -> Option<Shape> {
+        treap::shape::<(), Shared>(&self.root)
+    }
+}
+
+#[derive(Debug)]
+pub struct PersistentTreapIter<'a> {
+    prev_nodes: Vec<&'a TreapNode<(), i64, Shared>>,
+    current: &'a PersistentTreap,
+}
+
+impl<'a> Iterator for PersistentTreapIter<'a> {
+    type Item = &'a i64;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match *self.current {
+                None => match self.prev_nodes.pop() {
+                    Some(prev_node) => {
+                        self.current = &prev_node.right;
+                        return Some(&prev_node.value);
+                    }
+                    None => return None,
+                },
+                Some(ref node) => {
+                    if node.left.is_some() {
+                        self.prev_nodes.push(node);
+                        self.current = &node.left;
+                        continue;
+                    }
+                    if node.right.is_some() {
+                        self.current = &node.right;
+                        return Some(&node.value);
+                    }
+                    self.current = &None;
+                    return Some(&node.value);
+                }
+            }
+        }
+    }
+}
+
+type PersistentAvl = AvlTree<(), i64, Shared>;
+
+/// AVL tree whose versions share unchanged subtrees. Cloning is O(1).
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct PersistentAvlSet {
+    root: PersistentAvl,
+}
+
+impl PersistentAvlSet {
+    pub fn inserted(&self, value: i64) -> Self {
+        if self.search(value) {
+            return self.clone();
+        }
+        let mut root = self.root.clone();
+        avl::insert_by::<(), i64, Shared>(&mut root, value, &i64::cmp);
+        Self { root }
+    }
+
+    pub fn removed(&self, value: i64) -> Self {
+        if !self.search(value) {
+            return self.clone();
+        }
+        let mut root = self.root.clone();
+        avl::remove_by::<(), i64, Shared>(&mut root, value, &i64::cmp);
+        Self { root }
+    }
+
+    pub fn iter(&self) -> PersistentAvlIter<'_> {
+        PersistentAvlIter {
+            prev_nodes: vec![],
+            current: &self.root,
+        }
+    }
+}
+
+impl Tree for PersistentAvlSet {
+    fn insert(&mut self, value: i64) -> bool {
+        if self.search(value) {
+            return false;
+        }
+        avl::insert_by::<(), i64, Shared>(&mut self.root, value, &i64::cmp)
+    }
+
+    fn remove(&mut self, value: i64) -> Option<i64> {
+        if !self.search(value) {
+            return None;
+        }
+        avl::remove_by::<(), i64, Shared>(&mut self.root, value, &i64::cmp)
+    }
+
+    fn search(&self, value: i64) -> bool {
+        let mut current = &self.root;
+        while let Some(node) = current {
+            current = match node.value.cmp(&value) {
+                Ordering::Less => &node.right,
+                Ordering::Equal => return true,
+                Ordering::Greater => &node.left,
+            }
+        }
+        false
+    }
+
+    fn values(&self) -> Vec<i64> {
+        self.iter().copied().collect()
+    }
+
+    fn shape(&self) -> Option<Shape> {
+        avl::shape::<(), Shared>(&self.root)
+    }
+}
+
+#[derive(Debug)]
+pub struct PersistentAvlIter<'a> {
+    prev_nodes: Vec<&'a AvlNode<(), i64, Shared>>,
+    current: &'a PersistentAvl,
+}
+
+impl<'a> Iterator for PersistentAvlIter<'a> {
+    type Item = &'a i64;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match *self.current {
+                None => match self.prev_nodes.pop() {
+                    Some(prev_node) => {
+                        self.current = &prev_node.right;
+                        return Some(&prev_node.value);
+                    }
+                    None => return None,
+                },
+                Some(ref node) => {
+                    if node.left.is_some() {
+                        self.prev_nodes.push(node);
+                        self.current = &node.left;
+                        continue;
+                    }
+                    if node.right.is_some() {
+                        self.current = &node.right;
+                        return Some(&node.value);
+                    }
+                    self.current = &None;
+                    return Some(&node.value);
+                }
+            }
+        }
+    }
+}
+
+// Every `Arc` allocation also holds the strong and weak counters. Subtrees
+// shared with other versions are counted in full.
+
+impl HeapSize for PersistentTreapSet {
+    fn heap_size_bytes(&self) -> usize {
+        self.iter().count() * (ARC_COUNTERS + size_of::<TreapNode<(), i64, Shared>>())
+    }
+}
+
+impl HeapSize for PersistentAvlSet {
+    fn heap_size_bytes(&self) -> usize {
+        self.iter().count() * (ARC_COUNTERS + size_of::<AvlNode<(), i64, Shared>>())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_avl_versions() {
+        let mut tree = PersistentAvlSet::default();
+        for value in 0..100 {
+            tree.insert(value);
+        }
+        let snapshot = tree.clone();
+        let removed = tree.removed(42).removed(7);
+        tree.insert(1000);
+        assert_eq!(
+            (0..100).collect::<Vec<i64>>(),
+            snapshot.iter().copied().collect::<Vec<_>>()
+        );
+        assert!(!removed.search(42));
+        assert!(!removed.search(1000));
+        assert!(tree.search(42));
+        assert!(tree.search(1000));
+        assert!(tree.root.as_ref().unwrap().height <= 8);
+    }
+
+    #[test]
+    fn test_treap_versions() {
+        let mut tree = PersistentTreapSet::default();
+        for value in [42, 15, 21, 55, 48, 25, 18, 12, 8, 14, 96] {
+            tree.insert(value);
+        }
+        let snapshot = tree.clone();
+        assert!(!tree.insert(42));
+        assert_eq!(Some(15), tree.remove(15));
+        assert_eq!(None, tree.remove(15));
+        assert!(snapshot.search(15));
+        assert_eq!(
+            vec![8, 12, 14, 18, 21, 25, 42, 48, 55, 96],
+            tree.iter().copied().collect::<Vec<i64>>()
+        );
+        let inserted = snapshot.inserted(100);
+        assert!(inserted.search(100));
+        assert!(!snapshot.search(100));
+    }
+}
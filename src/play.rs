@@ -1,98 +1,152 @@
-use std::time::Instant;
+use std::{
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    thread,
+    time::Instant,
+};
 
 use rand::Rng;
 
-use crate::Tree;
+use crate::{
+    concurrent::SharedTree,
+    workload::{self, OpCount, Operation, Phase, Workload, WorkloadResult},
+    Tree,
+};
 
-pub fn play_random_numbers(tree: &mut dyn Tree, n: usize) {
-    let mut rng = rand::thread_rng();
-    let start_insert_random_numbers = Instant::now();
-    let mut inserted = 0;
-    for _ in 0..n {
-        if tree.insert(rng.gen_range(0..(3 * n)).try_into().unwrap()) {
-            inserted += 1;
+pub fn play_random_numbers(tree: &mut dyn Tree, n: usize) -> WorkloadResult {
+    play_workload(tree, &Workload::random(n))
+}
+
+pub fn play_sorted_numbers(tree: &mut dyn Tree, n: usize) -> WorkloadResult {
+    play_workload(tree, &Workload::sorted(n))
+}
+
+/// Runs `workload` and prints what happened in every phase.
+pub fn play_workload(tree: &mut dyn Tree, workload: &Workload) -> WorkloadResult {
+    let result = workload::run(tree, workload);
+    for phase in &result.phases {
+        let elapsed = phase.elapsed;
+        let OpCount {
+            attempted,
+            succeeded,
+        } = match phase.phase {
+            Phase::Only(operation) => phase.count(operation),
+            Phase::Mixed => OpCount::default(),
+        };
+        match phase.phase {
+            Phase::Only(Operation::Insert) => {
+                println!("Inserted {succeeded} out of {attempted} numbers in {elapsed:?}")
+            }
+            Phase::Only(Operation::Search) => {
+                println!("Searched for {attempted} numbers in {elapsed:?}. Found {succeeded}.")
+            }
+            Phase::Only(Operation::Remove) => {
+                println!("Removed {attempted} numbers in {elapsed:?}. Deleted {succeeded}.")
+            }
+            Phase::Mixed => {
+                let counts = Operation::ALL.map(|operation| {
+                    let count = phase.count(operation);
+                    format!("{operation} {}/{}", count.succeeded, count.attempted)
+                });
+                println!(
+                    "Made {} mixed operations in {elapsed:?} ({}).",
+                    phase.operations(),
+                    counts.join(", ")
+                )
+            }
         }
-    }
-    println!(
-        "Inserted {inserted} out of {n} random numbers in {:?}",
-        Instant::now().duration_since(start_insert_random_numbers)
-    );
-    let start_search = Instant::now();
-    let mut found = 0;
-    for _ in 0..n / 10 {
-        if tree.search(rng.gen_range(0..(3 * n)).try_into().unwrap()) {
-            found += 1;
+        for operation in Operation::ALL {
+            let latency = phase.latency(operation);
+            if !latency.is_empty() {
+                println!("    {operation} latency: {}", latency.summary());
+            }
         }
     }
-    println!(
-        "Searched for {} numbers in {:?}. Found {found}.",
-        n / 10,
-        Instant::now().duration_since(start_search)
-    );
-    let start_remove = Instant::now();
-    let mut removed = 0;
-    for _ in 0..n / 10 {
-        if tree
-            .remove(rng.gen_range(0..(3 * n)).try_into().unwrap())
-            .is_some()
-        {
-            removed += 1;
-        }
+    if let Some(usage) = result.memory {
+        // The tree may have held keys before the run.
+        let elements = result
+            .count(Operation::Insert)
+            .succeeded
+            .saturating_sub(result.count(Operation::Remove).succeeded);
+        println!(
+            "Peak memory {} bytes, retained {} bytes: {:.1} bytes per element.",
+            usage.peak,
+            usage.retained,
+            usage.retained as f64 / elements.max(1) as f64
+        );
     }
-    println!(
-        "Removed {} numbers in {:?}. Deleted {removed}.",
-        n / 10,
-        Instant::now().duration_since(start_remove)
-    );
+    result
 }
 
-pub fn play_sorted_numbers(tree: &mut dyn Tree, n: usize) {
-    let mut rng = rand::thread_rng();
-    let start_insert_sorted_numbers = Instant::now();
-    for x in 0..n {
-        tree.insert(x as _);
-    }
-    println!(
-        "Inserted {n} sorted numbers in {:?}",
-        Instant::now().duration_since(start_insert_sorted_numbers)
-    );
-    let start_search = Instant::now();
-    let mut found = 0;
-    for _ in 0..n / 10 {
-        if tree.search(rng.gen_range(0..n).try_into().unwrap()) {
-            found += 1;
+/// Inserts and removes random numbers from `writers` threads while `readers`
+/// threads search until the writers are done.
+///
+/// # Panics
+///
+/// If `writers` is zero, or if a writer panics.
+pub fn play_concurrent_numbers(tree: &dyn SharedTree, n: usize, readers: usize, writers: usize) {
+    assert!(writers > 0, "at least one writer is needed");
+    let writers_done = AtomicBool::new(false);
+    let searched = AtomicUsize::new(0);
+    let start = Instant::now();
+    let (mutated, writing_time) = thread::scope(|scope| {
+        for _ in 0..readers {
+            scope.spawn(|| {
+                let mut rng = rand::thread_rng();
+                let mut local = 0;
+                while !writers_done.load(Ordering::Relaxed) {
+                    tree.search(rng.gen_range(0..(3 * n)).try_into().unwrap());
+                    local += 1;
+                }
+                searched.fetch_add(local, Ordering::Relaxed);
+            });
         }
-    }
+        let handles = (0..writers)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut rng = rand::thread_rng();
+                    for _ in 0..n / writers {
+                        tree.insert(rng.gen_range(0..(3 * n)).try_into().unwrap());
+                    }
+                    for _ in 0..n / 10 / writers {
+                        tree.remove(rng.gen_range(0..(3 * n)).try_into().unwrap());
+                    }
+                    n / writers + n / 10 / writers
+                })
+            })
+            .collect::<Vec<_>>();
+        // Stops the readers even if joining a panicked writer unwinds.
+        let stop_readers = StopOnDrop(&writers_done);
+        let mutated = handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .sum::<usize>();
+        let writing_time = Instant::now().duration_since(start);
+        drop(stop_readers);
+        (mutated, writing_time)
+    });
+    let searched = searched.into_inner();
     println!(
-        "Searched for {} numbers in {:?}. Found {found}.",
-        n / 10,
-        Instant::now().duration_since(start_search)
+        "{writers} writers made {mutated} inserts and removes in {writing_time:?}: {:.0} ops/sec",
+        mutated as f64 / writing_time.as_secs_f64()
     );
-    let start_remove = Instant::now();
-    let mut removed = 0;
-    for _ in 0..n / 10 {
-        let to_delete = rng.gen_range(0..n).try_into().unwrap();
-        let result = tree.remove(to_delete);
-        if result.is_some() {
-            assert_eq!(to_delete, result.unwrap());
-            removed += 1;
-        }
-    }
     println!(
-        "Removed {} numbers in {:?}. Deleted {removed}.",
-        n / 10,
-        Instant::now().duration_since(start_remove)
+        "{readers} readers made {searched} searches meanwhile: {:.0} ops/sec",
+        searched as f64 / writing_time.as_secs_f64()
     );
 }
 
+struct StopOnDrop<'a>(&'a AtomicBool);
+
+impl Drop for StopOnDrop<'_> {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
 pub fn sorted_percent(mut data: impl Iterator<Item = i64>) -> usize {
     let start = data.next().unwrap();
     let (size, success, _) = data.fold((0_usize, 0_usize, start), |(sum, ok, prev), current| {
         (sum + 1, if prev > current { ok } else { ok + 1 }, current)
     });
-    if size > 0 {
-        success * 100 / size
-    } else {
-        100
-    }
+    (success * 100).checked_div(size).unwrap_or(100)
 }
@@ -0,0 +1,198 @@
+use std::{
+    cmp::{max, min},
+    ops::RangeBounds,
+};
+
+use crate::implicit_treap::{ImplicitTreap, Lazy};
+
+/// Sum, minimum and maximum of a range. The sum wraps around on overflow.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aggregate {
+    pub sum: i64,
+    pub min: i64,
+    pub max: i64,
+}
+
+/// Assignment followed by an addition. The default does neither.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Update {
+    pub assign: Option<i64>,
+    pub add: i64,
+}
+
+/// Range sum, minimum and maximum with range add and assign.
+///
+/// All arithmetic wraps around on overflow, the way `i64` does in release
+/// builds: sums stay exact modulo 2^64, while minimum and maximum are only
+/// meaningful as long as no element overflows.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RangeOps;
+
+impl Lazy<i64> for RangeOps {
+    type Summary = Aggregate;
+    type Tag = Update;
+
+    fn lift(value: &i64) -> Aggregate {
+        Aggregate {
+            sum: *value,
+            min: *value,
+            max: *value,
+        }
+    }
+
+    fn combine(left: &Aggregate, right: &Aggregate) -> Aggregate {
+        Aggregate {
+            sum: left.sum.wrapping_add(right.sum),
+            min: min(left.min, right.min),
+            max: max(left.max, right.max),
+        }
+    }
+
+    fn apply(tag: &Update, value: &mut i64) {
+        if let Some(assign) = tag.assign {
+            *value = assign;
+        }
+        *value = value.wrapping_add(tag.add);
+    }
+
+    fn apply_summary(tag: &Update, summary: &mut Aggregate, size: usize) {
+        if let Some(assign) = tag.assign {
+            *summary = Aggregate {
+                sum: assign.wrapping_mul(size as i64),
+                min: assign,
+                max: assign,
+            };
+        }
+        summary.sum = summary.sum.wrapping_add(tag.add.wrapping_mul(size as i64));
+        summary.min = summary.min.wrapping_add(tag.add);
+        summary.max = summary.max.wrapping_add(tag.add);
+    }
+
+    fn compose(tag: &mut Update, newer: &Update) {
+        if newer.assign.is_some() {
+            *tag = *newer;
+        } else {
+            tag.add = tag.add.wrapping_add(newer.add);
+        }
+    }
+}
+
+/// Implicit treap over `i64` with lazy range updates and range aggregates.
+///
+/// Ranges are positions in the sequence, not values.
+pub type RangeTreap = ImplicitTreap<i64, RangeOps>;
+
+impl ImplicitTreap<i64, RangeOps> {
+    pub fn get(&self, index: usize) -> Option<i64> {
+        self.value_at(index)
+    }
+
+    pub fn add(&mut self, range: impl RangeBounds<usize>, delta: i64) {
+        self.update(
+            range,
+            Update {
+                assign: None,
+                add: delta,
+            },
+        );
+    }
+
+    pub fn assign(&mut self, range: impl RangeBounds<usize>, value: i64) {
+        self.update(
+            range,
+            Update {
+                assign: Some(value),
+                add: 0,
+            },
+        );
+    }
+
+    /// Sum over `range`. Empty range sums to zero.
+    pub fn sum(&self, range: impl RangeBounds<usize>) -> i64 {
+        self.query(range).map_or(0, |aggregate| aggregate.sum)
+    }
+
+    pub fn min(&self, range: impl RangeBounds<usize>) -> Option<i64> {
+        self.query(range).map(|aggregate| aggregate.min)
+    }
+
+    pub fn max(&self, range: impl RangeBounds<usize>) -> Option<i64> {
+        self.query(range).map(|aggregate| aggregate.max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Bound;
+
+    use rand::Rng;
+
+    use super::*;
+
+    #[test]
+    fn test_range_operations() {
+        let mut treap = (1..=10).collect::<RangeTreap>();
+        assert_eq!(55, treap.sum(..));
+        treap.reverse(2..6);
+        assert_eq!(vec![1, 2, 6, 5, 4, 3, 7, 8, 9, 10], treap.to_vec());
+        treap.add(..3, 10);
+        assert_eq!(Some(16), treap.max(0..3));
+        treap.assign(5..=7, -1);
+        assert_eq!(vec![11, 12, 16, 5, 4, -1, -1, -1, 9, 10], treap.to_vec());
+        assert_eq!(Some(-1), treap.min(..));
+        assert_eq!(None, treap.min(4..4));
+        assert_eq!(0, treap.sum(10..));
+        assert_eq!(Some(-1), treap.get(7));
+        assert_eq!(None, treap.get(10));
+        assert_eq!(
+            None,
+            treap.max((Bound::Excluded(usize::MAX), Bound::Unbounded))
+        );
+        assert_eq!(10, treap.sum(9..=usize::MAX));
+    }
+
+    #[test]
+    fn test_wrapping() {
+        let mut treap = [i64::MAX, 1].into_iter().collect::<RangeTreap>();
+        assert_eq!(i64::MIN, treap.sum(..));
+        treap.assign(.., i64::MAX);
+        assert_eq!(-2, treap.sum(..));
+        treap.add(.., 1);
+        let treap = treap;
+        assert_eq!(0, treap.sum(..));
+        assert_eq!(Some(i64::MIN), treap.max(1..));
+        assert_eq!(vec![i64::MIN; 2], treap.to_vec());
+    }
+
+    #[test]
+    fn test_against_vec() {
+        let mut rng = rand::thread_rng();
+        let mut expected = (0..200).collect::<Vec<i64>>();
+        let mut treap = expected.iter().copied().collect::<RangeTreap>();
+        for _ in 0..1000 {
+            let l = rng.gen_range(0..expected.len());
+            let r = rng.gen_range(l..=expected.len());
+            match rng.gen_range(0..4) {
+                0 => {
+                    expected[l..r].reverse();
+                    treap.reverse(l..r);
+                }
+                1 => {
+                    expected[l..r].iter_mut().for_each(|x| *x += 3);
+                    treap.add(l..r, 3);
+                }
+                2 => {
+                    expected[l..r].iter_mut().for_each(|x| *x = l as i64);
+                    treap.assign(l..r, l as i64);
+                }
+                _ => {
+                    assert_eq!(expected.get(l).copied(), treap.get(l));
+                    assert_eq!(expected[l..r].iter().sum::<i64>(), treap.sum(l..r));
+                    assert_eq!(expected[l..r].iter().min().copied(), treap.min(l..r));
+                    assert_eq!(expected[l..r].iter().max().copied(), treap.max(l..r));
+                }
+            }
+        }
+        assert_eq!(expected, treap.to_vec());
+    }
+}
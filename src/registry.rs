@@ -0,0 +1,154 @@
+use crate::{
+    arena::{ArenaAvlSet, ArenaTreapSet},
+    avl::AvlSet,
+    bst::BstSet,
+    multiset::{AvlMultiSet, TreapMultiSet},
+    persistent::{PersistentAvlSet, PersistentTreapSet},
+    skiplist::SkipList,
+    treap::TreapSet,
+    Tree,
+};
+
+/// A `Tree` that can copy itself behind a `Box`, which `undo` in the REPL
+/// relies on.
+pub trait CloneTree: Tree {
+    fn clone_tree(&self) -> Box<dyn CloneTree>;
+}
+
+impl<T: Tree + Clone + 'static> CloneTree for T {
+    fn clone_tree(&self) -> Box<dyn CloneTree> {
+        Box::new(self.clone())
+    }
+}
+
+/// A structure that can be picked by name on the command line.
+#[derive(Debug, Clone, Copy)]
+pub struct TreeFactory {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// Rust type behind the name, for generated code.
+    pub type_name: &'static str,
+    /// Whether inserting a stored value adds another copy instead of failing.
+    pub keeps_duplicates: bool,
+    pub build: fn() -> Box<dyn CloneTree>,
+}
+
+pub static TREES: &[TreeFactory] = &[
+    TreeFactory {
+        name: "bst",
+        description: "unbalanced binary search tree",
+        type_name: "BstSet",
+        keeps_duplicates: false,
+        build: || Box::<BstSet>::default(),
+    },
+    TreeFactory {
+        name: "avl",
+        description: "AVL tree",
+        type_name: "AvlSet",
+        keeps_duplicates: false,
+        build: || Box::<AvlSet>::default(),
+    },
+    TreeFactory {
+        name: "treap",
+        description: "treap",
+        type_name: "TreapSet",
+        keeps_duplicates: false,
+        build: || Box::<TreapSet>::default(),
+    },
+    TreeFactory {
+        name: "arena-avl",
+        description: "AVL tree with nodes in an arena",
+        type_name: "ArenaAvlSet",
+        keeps_duplicates: false,
+        build: || Box::<ArenaAvlSet>::default(),
+    },
+    TreeFactory {
+        name: "arena-treap",
+        description: "treap with nodes in an arena",
+        type_name: "ArenaTreapSet",
+        keeps_duplicates: false,
+        build: || Box::<ArenaTreapSet>::default(),
+    },
+    TreeFactory {
+        name: "avl-multiset",
+        description: "AVL tree that keeps duplicates",
+        type_name: "AvlMultiSet",
+        keeps_duplicates: true,
+        build: || Box::<AvlMultiSet>::default(),
+    },
+    TreeFactory {
+        name: "treap-multiset",
+        description: "treap that keeps duplicates",
+        type_name: "TreapMultiSet",
+        keeps_duplicates: true,
+        build: || Box::<TreapMultiSet>::default(),
+    },
+    TreeFactory {
+        name: "persistent-avl",
+        description: "persistent AVL tree with shared nodes",
+        type_name: "PersistentAvlSet",
+        keeps_duplicates: false,
+        build: || Box::<PersistentAvlSet>::default(),
+    },
+    TreeFactory {
+        name: "persistent-treap",
+        description: "persistent treap with shared nodes",
+        type_name: "PersistentTreapSet",
+        keeps_duplicates: false,
+        build: || Box::<PersistentTreapSet>::default(),
+    },
+    TreeFactory {
+        name: "skiplist",
+        description: "lock-free skip list",
+        type_name: "SkipList",
+        keeps_duplicates: false,
+        build: || Box::<SkipList>::default(),
+    },
+];
+
+pub fn find(name: &str) -> Option<&'static TreeFactory> {
+    TREES.iter().find(|factory| factory.name == name)
+}
+
+/// Factories for a comma separated list of names.
+pub fn find_all(names: &str) -> Result<Vec<&'static TreeFactory>, String> {
+    names
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(|name| find(name).ok_or_else(|| format!("unknown tree {name:?}")))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry() {
+        for factory in TREES {
+            let mut tree = (factory.build)();
+            for value in [5, 3, 8, 3] {
+                tree.insert(value);
+            }
+            assert!(tree.search(8), "{}", factory.name);
+            assert_eq!(Some(8), tree.remove(8), "{}", factory.name);
+            let expected = if factory.keeps_duplicates {
+                vec![3, 3, 5]
+            } else {
+                vec![3, 5]
+            };
+            assert_eq!(expected, tree.values(), "{}", factory.name);
+            assert_eq!(factory.name == "skiplist", tree.shape().is_none());
+        }
+        assert_eq!(
+            vec!["avl", "treap"],
+            find_all("avl, treap")
+                .unwrap()
+                .iter()
+                .map(|factory| factory.name)
+                .collect::<Vec<_>>()
+        );
+        assert!(find_all("avl,rb").is_err());
+    }
+}
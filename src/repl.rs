@@ -0,0 +1,202 @@
+use crate::{
+    registry::{CloneTree, TreeFactory},
+    viz::{self, draw},
+    Tree,
+};
+
+pub const HELP: &str = "\
+insert <key...>   add keys, nodes moved by rebalancing are shown in [brackets]
+remove <key...>   remove keys
+search <key...>   look keys up
+range <from> <to> keys in from..=to
+rank <key>        how many keys are smaller
+undo              take back the last insert or remove
+show              draw the tree again
+help, quit";
+
+const COMMANDS: &[&str] = &[
+    "insert", "i", "remove", "r", "search", "s", "range", "rank", "undo", "show", "help", "quit",
+    "q", "exit",
+];
+
+/// An interactive session over one tree. Every command answers with a few
+/// lines of text followed by the redrawn tree.
+pub struct Session {
+    name: &'static str,
+    tree: Box<dyn CloneTree>,
+    history: Vec<Box<dyn CloneTree>>,
+}
+
+impl Session {
+    pub fn new(factory: &TreeFactory) -> Self {
+        Self {
+            name: factory.name,
+            tree: (factory.build)(),
+            history: vec![],
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn tree(&self) -> &dyn Tree {
+        self.tree.as_ref()
+    }
+
+    /// Runs one line of input. `None` means the user asked to quit.
+    pub fn execute(&mut self, line: &str) -> Option<String> {
+        let mut words = line.split_whitespace();
+        let Some(command) = words.next() else {
+            return Some(String::new());
+        };
+        let Ok(keys) = words.map(str::parse::<i64>).collect::<Result<Vec<_>, _>>() else {
+            return Some("keys must be integers".to_string());
+        };
+        let mut lines = vec![];
+        let mut highlight = vec![];
+        match (command, keys.as_slice()) {
+            ("insert" | "i", [_, ..]) => {
+                highlight = self.mutate(&keys, &mut lines, |tree, key| {
+                    let inserted = tree.insert(key);
+                    (format!("insert {key}: {inserted}"), inserted)
+                })
+            }
+            ("remove" | "r", [_, ..]) => {
+                highlight = self.mutate(&keys, &mut lines, |tree, key| {
+                    let removed = tree.remove(key).is_some();
+                    (format!("remove {key}: {removed}"), removed)
+                })
+            }
+            ("search" | "s", [_, ..]) => {
+                for &key in &keys {
+                    let found = self.tree.search(key);
+                    lines.push(format!("search {key}: {found}"));
+                    if found {
+                        highlight.push(key);
+                    }
+                }
+            }
+            ("range", &[from, to]) => {
+                highlight = self
+                    .tree
+                    .values()
+                    .into_iter()
+                    .filter(|value| (from..=to).contains(value))
+                    .collect();
+                lines.push(format!("range {from}..={to}: {highlight:?}"));
+            }
+            ("rank", &[key]) => {
+                highlight = self
+                    .tree
+                    .values()
+                    .into_iter()
+                    .filter(|value| *value < key)
+                    .collect();
+                lines.push(format!("rank {key}: {}", highlight.len()));
+            }
+            ("undo", []) => match self.history.pop() {
+                Some(tree) => {
+                    self.tree = tree;
+                    lines.push("undone".to_string());
+                }
+                None => lines.push("nothing to undo".to_string()),
+            },
+            ("show", []) => {}
+            ("help", []) => return Some(HELP.to_string()),
+            ("quit" | "q" | "exit", []) => return None,
+            _ if COMMANDS.contains(&command) => {
+                return Some(format!("wrong arguments for {command}, try help"))
+            }
+            _ => return Some(format!("unknown command {command:?}, try help")),
+        }
+        lines.push(draw(self.tree(), &highlight));
+        Some(lines.join("\n"))
+    }
+
+    // Applies `operation` to every key, keeping a snapshot for `undo` when
+    // something changed. Returns the nodes that some of the operations moved.
+    fn mutate(
+        &mut self,
+        keys: &[i64],
+        lines: &mut Vec<String>,
+        operation: impl Fn(&mut dyn Tree, i64) -> (String, bool),
+    ) -> Vec<i64> {
+        let snapshot = self.tree.clone_tree();
+        let mut changed = false;
+        let mut moved = vec![];
+        for &key in keys {
+            let before = self.tree.shape();
+            let (line, done) = operation(self.tree.as_mut(), key);
+            lines.push(line);
+            changed |= done;
+            if let (Some(before), Some(after)) = (before, self.tree.shape()) {
+                moved.extend(viz::moved(&before, &after));
+            }
+        }
+        moved.sort_unstable();
+        moved.dedup();
+        if !moved.is_empty() {
+            lines.push(format!("moved: {moved:?}"));
+        }
+        if changed {
+            self.history.push(snapshot);
+        }
+        moved
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::registry;
+
+    use super::*;
+
+    #[test]
+    fn test_session() {
+        let mut session = Session::new(registry::find("avl").unwrap());
+        assert!(session
+            .execute("insert 1 2 3")
+            .unwrap()
+            .contains("moved: [1, 2]"));
+        session.execute("undo");
+        assert_eq!(
+            "insert 3: true\ninsert 2: true\n  3\n /\n2",
+            session.execute("insert 3 2").unwrap()
+        );
+        assert_eq!(
+            "insert 1: true\nmoved: [2, 3]\n  [2]_\n /    \\\n1     [3]",
+            session.execute("i 1").unwrap()
+        );
+        assert_eq!(
+            "insert 1: false\n  2\n / \\\n1   3",
+            session.execute("insert 1").unwrap()
+        );
+        assert!(session
+            .execute("search 3 7")
+            .unwrap()
+            .starts_with("search 3: true\nsearch 7: false\n  2_\n /  \\\n1   [3]"));
+        assert!(session
+            .execute("range 2 10")
+            .unwrap()
+            .starts_with("range 2..=10: [2, 3]"));
+        assert!(session.execute("rank 3").unwrap().starts_with("rank 3: 2"));
+        assert!(session.execute("undo").unwrap().ends_with("  3\n /\n2"));
+        assert!(session.execute("undo").unwrap().ends_with("(empty)"));
+        assert!(session
+            .execute("undo")
+            .unwrap()
+            .starts_with("nothing to undo"));
+        assert!(session.execute("range 1").unwrap().starts_with("wrong"));
+        assert!(session.execute("insert x").unwrap().starts_with("keys"));
+        assert!(session.execute("quit").is_none());
+    }
+
+    #[test]
+    fn test_treap_priorities() {
+        let mut session = Session::new(registry::find("treap").unwrap());
+        let drawing = session.execute("insert 5").unwrap();
+        let priority = drawing.lines().last().unwrap().strip_prefix("5:").unwrap();
+        assert!(priority.parse::<u32>().unwrap() < 100);
+    }
+}
@@ -0,0 +1,536 @@
+use std::{
+    collections::HashSet,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crossbeam_epoch::{self as epoch, Atomic, Guard, Owned, Shared};
+use rand::Rng;
+
+use crate::{concurrent::SharedTree, memory::HeapSize, Tree};
+
+const MAX_HEIGHT: usize = 24;
+
+// A tag of 1 on `next[level]` marks the node as removed on that level.
+// Level 0 decides: whoever marks it has removed the value.
+//
+// `links` counts the levels the node is linked on, plus one while its
+// inserter is still linking the upper levels. Whoever drops it to zero has
+// made the node unreachable and retires it.
+struct Node {
+    value: i64,
+    links: AtomicUsize,
+    next: Box<[Atomic<Node>]>,
+}
+
+impl Node {
+    pub fn new(value: i64, height: usize) -> Self {
+        Self {
+            value,
+            // Level 0 and the inserter.
+            links: AtomicUsize::new(2),
+            next: (0..height).map(|_| Atomic::null()).collect(),
+        }
+    }
+}
+
+fn release(node: Shared<'_, Node>, guard: &Guard) {
+    if unsafe { node.deref() }.links.fetch_sub(1, Ordering::AcqRel) == 1 {
+        unsafe { guard.defer_destroy(node) };
+    }
+}
+
+fn random_height() -> usize {
+    let mut rng = rand::thread_rng();
+    let mut height = 1;
+    while height < MAX_HEIGHT && rng.gen::<bool>() {
+        height += 1;
+    }
+    height
+}
+
+// Towers of `next` pointers right before and at-or-after the searched value.
+struct Position<'g> {
+    found: bool,
+    preds: [&'g [Atomic<Node>]; MAX_HEIGHT],
+    succs: [Shared<'g, Node>; MAX_HEIGHT],
+}
+
+/// Lock-free skip list in the style of Harris and Fraser.
+///
+/// Removal marks the tower top-down and then level 0; every search unlinks
+/// marked nodes it steps on. A node is handed to epoch-based reclamation by
+/// whoever unlinks it from its last level, not by the remover, since an
+/// inserter may still be linking it higher up.
+pub struct SkipList {
+    head: Box<[Atomic<Node>]>,
+}
+
+impl Default for SkipList {
+    fn default() -> Self {
+        Self {
+            head: (0..MAX_HEIGHT).map(|_| Atomic::null()).collect(),
+        }
+    }
+}
+
+/// Copies what one pass over level 0 sees. Values inserted or removed by
+/// other threads during the pass may or may not make it into the copy.
+impl Clone for SkipList {
+    fn clone(&self) -> Self {
+        let copy = Self::default();
+        for value in self.iter() {
+            copy.insert(value);
+        }
+        copy
+    }
+}
+
+impl SkipList {
+    pub fn insert(&self, value: i64) -> bool {
+        self.insert_with_height(value, random_height())
+    }
+
+    fn insert_with_height(&self, value: i64, height: usize) -> bool {
+        let guard = &epoch::pin();
+        let mut node = Owned::new(Node::new(value, height));
+        let (node, position) = loop {
+            let position = self.find(value, guard);
+            if position.found {
+                return false;
+            }
+            node.next[0].store(position.succs[0], Ordering::Relaxed);
+            match position.preds[0][0].compare_exchange(
+                position.succs[0],
+                node,
+                Ordering::Release,
+                Ordering::Relaxed,
+                guard,
+            ) {
+                Ok(node) => break (node, position),
+                Err(error) => node = error.new,
+            }
+        };
+        self.link_upper_levels(node, position, guard);
+        release(node, guard);
+        true
+    }
+
+    // Links the tower of a node already on level 0, bottom-up, until it is
+    // complete or the node gets removed.
+    fn link_upper_levels<'g>(
+        &'g self,
+        node: Shared<'g, Node>,
+        mut position: Position<'g>,
+        guard: &'g Guard,
+    ) {
+        let node_ref = unsafe { node.deref() };
+        for level in 1..node_ref.next.len() {
+            loop {
+                let next = node_ref.next[level].load(Ordering::Acquire, guard);
+                if next.tag() == 1 {
+                    return;
+                }
+                let succ = position.succs[level];
+                if node_ref.next[level]
+                    .compare_exchange(next, succ, Ordering::Release, Ordering::Relaxed, guard)
+                    .is_err()
+                {
+                    return;
+                }
+                node_ref.links.fetch_add(1, Ordering::AcqRel);
+                if position.preds[level][level]
+                    .compare_exchange(succ, node, Ordering::Release, Ordering::Relaxed, guard)
+                    .is_ok()
+                {
+                    break;
+                }
+                // Our own hold keeps the count above zero.
+                node_ref.links.fetch_sub(1, Ordering::AcqRel);
+                position = self.find(node_ref.value, guard);
+                if position.succs[0] != node {
+                    return;
+                }
+            }
+            // A remover may have marked this level between our two CASes and
+            // already passed by. Unlink the node ourselves.
+            if node_ref.next[level].load(Ordering::Acquire, guard).tag() == 1 {
+                self.find(node_ref.value, guard);
+                return;
+            }
+        }
+    }
+
+    pub fn remove(&self, value: i64) -> Option<i64> {
+        let guard = &epoch::pin();
+        let position = self.find(value, guard);
+        if !position.found {
+            return None;
+        }
+        let node = position.succs[0];
+        let node_ref = unsafe { node.deref() };
+        for level in (1..node_ref.next.len()).rev() {
+            let mut next = node_ref.next[level].load(Ordering::Acquire, guard);
+            while next.tag() == 0 {
+                match node_ref.next[level].compare_exchange(
+                    next,
+                    next.with_tag(1),
+                    Ordering::AcqRel,
+                    Ordering::Acquire,
+                    guard,
+                ) {
+                    Ok(_) => break,
+                    Err(error) => next = error.current,
+                }
+            }
+        }
+        let mut next = node_ref.next[0].load(Ordering::Acquire, guard);
+        loop {
+            if next.tag() == 1 {
+                return None;
+            }
+            match node_ref.next[0].compare_exchange(
+                next,
+                next.with_tag(1),
+                Ordering::AcqRel,
+                Ordering::Acquire,
+                guard,
+            ) {
+                Ok(_) => break,
+                Err(error) => next = error.current,
+            }
+        }
+        self.find(value, guard);
+        Some(value)
+    }
+
+    pub fn contains(&self, value: i64) -> bool {
+        let guard = &epoch::pin();
+        self.find(value, guard).found
+    }
+
+    fn find<'g>(&'g self, value: i64, guard: &'g Guard) -> Position<'g> {
+        'retry: loop {
+            let mut position = Position {
+                found: false,
+                preds: [&self.head[..]; MAX_HEIGHT],
+                succs: [Shared::null(); MAX_HEIGHT],
+            };
+            let mut pred = &self.head[..];
+            for level in (0..MAX_HEIGHT).rev() {
+                let mut current = pred[level].load(Ordering::Acquire, guard);
+                if current.tag() == 1 {
+                    continue 'retry;
+                }
+                while let Some(current_ref) = unsafe { current.as_ref() } {
+                    let succ = current_ref.next[level].load(Ordering::Acquire, guard);
+                    if succ.tag() == 1 {
+                        match pred[level].compare_exchange(
+                            current,
+                            succ.with_tag(0),
+                            Ordering::AcqRel,
+                            Ordering::Acquire,
+                            guard,
+                        ) {
+                            Ok(_) => {
+                                release(current, guard);
+                                current = succ.with_tag(0);
+                            }
+                            Err(_) => continue 'retry,
+                        }
+                        continue;
+                    }
+                    if current_ref.value >= value {
+                        break;
+                    }
+                    pred = &current_ref.next;
+                    current = succ;
+                }
+                position.preds[level] = pred;
+                position.succs[level] = current;
+            }
+            position.found =
+                unsafe { position.succs[0].as_ref() }.is_some_and(|node| node.value == value);
+            return position;
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = i64> {
+        let guard = epoch::pin();
+        let mut values = vec![];
+        let mut current = self.head[0].load(Ordering::Acquire, &guard);
+        while let Some(node) = unsafe { current.as_ref() } {
+            let next = node.next[0].load(Ordering::Acquire, &guard);
+            if next.tag() == 0 {
+                values.push(node.value);
+            }
+            current = next.with_tag(0);
+        }
+        values.into_iter()
+    }
+}
+
+impl Drop for SkipList {
+    fn drop(&mut self) {
+        // Nobody else can see the list any more, and retired nodes were unlinked
+        // from every level before being handed to the collector. A removed node
+        // may still hang on upper levels only, so every level is walked.
+        let guard = unsafe { epoch::unprotected() };
+        let mut nodes = HashSet::new();
+        for level in 0..MAX_HEIGHT {
+            let mut current = self.head[level].load(Ordering::Relaxed, guard);
+            while !current.is_null() {
+                nodes.insert(current.as_raw());
+                current = unsafe { current.deref() }.next[level]
+                    .load(Ordering::Relaxed, guard)
+                    .with_tag(0);
+            }
+        }
+        for node in nodes {
+            drop(unsafe { Owned::from_raw(node.cast_mut()) });
+        }
+    }
+}
+
+impl SharedTree for SkipList {
+    fn insert(&self, value: i64) -> bool {
+        SkipList::insert(self, value)
+    }
+
+    fn remove(&self, value: i64) -> Option<i64> {
+        SkipList::remove(self, value)
+    }
+
+    fn search(&self, value: i64) -> bool {
+        self.contains(value)
+    }
+}
+
+impl Tree for SkipList {
+    fn insert(&mut self, value: i64) -> bool {
+        SkipList::insert(self, value)
+    }
+
+    fn remove(&mut self, value: i64) -> Option<i64> {
+        SkipList::remove(self, value)
+    }
+
+    fn search(&self, value: i64) -> bool {
+        self.contains(value)
+    }
+
+    fn values(&self) -> Vec<i64> {
+        self.iter().collect()
+    }
+}
+
+impl HeapSize for SkipList {
+    fn heap_size_bytes(&self) -> usize {
+        let guard = epoch::pin();
+        let tower = |height: usize| height * size_of::<Atomic<Node>>();
+        let mut bytes = tower(self.head.len());
+        let mut current = self.head[0].load(Ordering::Acquire, &guard);
+        while let Some(node) = unsafe { current.as_ref() } {
+            bytes += size_of::<Node>() + tower(node.next.len());
+            current = node.next[0].load(Ordering::Acquire, &guard).with_tag(0);
+        }
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::BTreeSet,
+        sync::atomic::{AtomicUsize, Ordering},
+        thread,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_single_thread() {
+        let list = SkipList::default();
+        let mut expected = BTreeSet::new();
+        let mut rng = rand::thread_rng();
+        for _ in 0..10000 {
+            let value = rng.gen_range(0..500);
+            if rng.gen::<bool>() {
+                assert_eq!(expected.insert(value), list.insert(value));
+            } else {
+                assert_eq!(expected.remove(&value).then_some(value), list.remove(value));
+            }
+        }
+        assert_eq!(
+            expected.into_iter().collect::<Vec<_>>(),
+            list.iter().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_stress() {
+        let list = SkipList::default();
+        thread::scope(|scope| {
+            for thread in 0..8_i64 {
+                let list = &list;
+                scope.spawn(move || {
+                    let mut rng = rand::thread_rng();
+                    for _ in 0..20000 {
+                        // Contended keys shared by everybody.
+                        let value = rng.gen_range(0..64);
+                        match rng.gen_range(0..3) {
+                            0 => list.insert(value),
+                            1 => list.remove(value).is_some(),
+                            _ => list.contains(value),
+                        };
+                    }
+                    // Keys owned by this thread must end up exactly as written.
+                    for value in (1000 * (thread + 1)..).take(300) {
+                        assert!(list.insert(value));
+                    }
+                    for value in (1000 * (thread + 1)..).take(300).step_by(2) {
+                        assert_eq!(Some(value), list.remove(value));
+                    }
+                });
+            }
+        });
+        let values = list.iter().collect::<Vec<_>>();
+        assert!(values.windows(2).all(|pair| pair[0] < pair[1]));
+        let owned = values.iter().filter(|value| **value >= 1000).count();
+        assert_eq!(8 * 150, owned);
+    }
+
+    #[test]
+    fn test_stress_tall_towers() {
+        // Few keys and full-height towers keep inserters linking upper levels
+        // while removers unlink the same nodes, which is where a node used to
+        // be retired while still reachable.
+        let list = SkipList::default();
+        thread::scope(|scope| {
+            for _ in 0..8 {
+                let list = &list;
+                scope.spawn(move || {
+                    let mut rng = rand::thread_rng();
+                    for _ in 0..20000 {
+                        let value = rng.gen_range(0..4);
+                        match rng.gen_range(0..3) {
+                            0 => list.insert_with_height(value, MAX_HEIGHT - rng.gen_range(0..4)),
+                            1 => list.remove(value).is_some(),
+                            _ => list.contains(value),
+                        };
+                    }
+                });
+            }
+        });
+        let values = list.iter().collect::<Vec<_>>();
+        assert!(values.windows(2).all(|pair| pair[0] < pair[1]));
+        for value in 0..4 {
+            assert_eq!(values.contains(&value), list.contains(value));
+            assert_eq!(values.contains(&value), list.remove(value).is_some());
+        }
+        assert!(list.iter().next().is_none());
+    }
+
+    #[derive(Debug, Clone, Copy)]
+    enum Op {
+        Insert,
+        Remove,
+        Contains,
+    }
+
+    #[derive(Debug, Clone, Copy)]
+    struct Event {
+        invoked: usize,
+        returned: usize,
+        op: Op,
+        result: bool,
+    }
+
+    // Wing & Gong search for a sequential order of the events on one key that
+    // respects real time and explains every result.
+    fn linearizable(events: &[Event], done: &mut Vec<bool>, present: bool) -> bool {
+        if done.iter().all(|done| *done) {
+            return true;
+        }
+        let first_return = (0..events.len())
+            .filter(|&i| !done[i])
+            .map(|i| events[i].returned)
+            .min()
+            .unwrap();
+        for i in 0..events.len() {
+            if done[i] || events[i].invoked > first_return {
+                continue;
+            }
+            let (expected, next) = match events[i].op {
+                Op::Insert => (!present, true),
+                Op::Remove => (present, false),
+                Op::Contains => (present, present),
+            };
+            if events[i].result != expected {
+                continue;
+            }
+            done[i] = true;
+            if linearizable(events, done, next) {
+                return true;
+            }
+            done[i] = false;
+        }
+        false
+    }
+
+    #[test]
+    fn test_linearizability() {
+        for _ in 0..300 {
+            let list = SkipList::default();
+            let clock = AtomicUsize::new(0);
+            let history = thread::scope(|scope| {
+                let handles = (0..3)
+                    .map(|_| {
+                        scope.spawn(|| {
+                            let mut rng = rand::thread_rng();
+                            (0..4)
+                                .map(|_| {
+                                    let value = rng.gen_range(0..2);
+                                    let op =
+                                        [Op::Insert, Op::Remove, Op::Contains][rng.gen_range(0..3)];
+                                    let invoked = clock.fetch_add(1, Ordering::SeqCst);
+                                    let result = match op {
+                                        Op::Insert => list.insert(value),
+                                        Op::Remove => list.remove(value).is_some(),
+                                        Op::Contains => list.contains(value),
+                                    };
+                                    let returned = clock.fetch_add(1, Ordering::SeqCst);
+                                    (
+                                        value,
+                                        Event {
+                                            invoked,
+                                            returned,
+                                            op,
+                                            result,
+                                        },
+                                    )
+                                })
+                                .collect::<Vec<_>>()
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                handles
+                    .into_iter()
+                    .flat_map(|handle| handle.join().unwrap())
+                    .collect::<Vec<_>>()
+            });
+            // Linearizability is local, so every key can be checked on its own.
+            for key in 0..2 {
+                let events = history
+                    .iter()
+                    .filter(|(value, _)| *value == key)
+                    .map(|(_, event)| *event)
+                    .collect::<Vec<_>>();
+                let mut done = vec![false; events.len()];
+                assert!(
+                    linearizable(&events, &mut done, false),
+                    "not linearizable: {events:?}"
+                );
+            }
+        }
+    }
+}
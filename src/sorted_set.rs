@@ -0,0 +1,223 @@
+use std::{cmp::Ordering, iter::Copied, vec};
+
+use crate::comparator::Comparator;
+
+/// Keys of a set in the order of some comparator: as they come if the set
+/// already uses it, collected and sorted otherwise.
+pub(crate) enum Keys<I> {
+    Ordered(I),
+    Sorted(vec::IntoIter<i64>),
+}
+
+impl<'a, I: Iterator<Item = &'a i64>> Iterator for Keys<Copied<I>> {
+    type Item = i64;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Self::Ordered(keys) => keys.next(),
+            Self::Sorted(keys) => keys.next(),
+        }
+    }
+}
+
+/// `keys`, iterated in the order of `keys_cmp`, rearranged into the order of
+/// `cmp`.
+pub(crate) fn in_order<'a, I: Iterator<Item = &'a i64>>(
+    cmp: &Comparator,
+    keys_cmp: &Comparator,
+    keys: I,
+) -> Keys<Copied<I>> {
+    if cmp == keys_cmp {
+        return Keys::Ordered(keys.copied());
+    }
+    let mut sorted = keys.copied().collect::<Vec<i64>>();
+    sorted.sort_by(|a, b| cmp.compare(a, b));
+    Keys::Sorted(sorted.into_iter())
+}
+
+/// Keys in natural order, whatever the comparator of the set. Sets are
+/// compared and hashed by their keys, so a set ordered in reverse equals
+/// the natural one holding the same keys.
+pub(crate) fn natural_keys<'a, I: Iterator<Item = &'a i64>>(
+    cmp: &Comparator,
+    keys: I,
+) -> Keys<Copied<I>> {
+    in_order(&Comparator::natural(), cmp, keys)
+}
+
+#[derive(Clone, Copy)]
+pub(crate) enum SetOp {
+    Union,
+    Intersection,
+    Difference,
+}
+
+/// Merges two key sequences sorted by `cmp` in a single pass.
+pub(crate) fn merge(
+    op: SetOp,
+    cmp: &Comparator,
+    lhs: impl Iterator<Item = i64>,
+    rhs: impl Iterator<Item = i64>,
+) -> Vec<i64> {
+    let (mut lhs, mut rhs) = (lhs.peekable(), rhs.peekable());
+    let mut merged = vec![];
+    loop {
+        let order = match (lhs.peek(), rhs.peek()) {
+            (None, None) => return merged,
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (Some(a), Some(b)) => cmp.compare(a, b),
+        };
+        match (order, op) {
+            (Ordering::Less, SetOp::Union | SetOp::Difference) => merged.extend(lhs.next()),
+            (Ordering::Less, SetOp::Intersection) => {
+                lhs.next();
+            }
+            (Ordering::Greater, SetOp::Union) => merged.extend(rhs.next()),
+            (Ordering::Greater, _) => {
+                rhs.next();
+            }
+            (Ordering::Equal, SetOp::Union | SetOp::Intersection) => {
+                merged.extend(lhs.next());
+                rhs.next();
+            }
+            (Ordering::Equal, SetOp::Difference) => {
+                lhs.next();
+                rhs.next();
+            }
+        }
+    }
+}
+
+/// Lays keys sorted by the comparator of a set out as a balanced tree in
+/// O(n). The middle key goes to the root, so sibling heights differ by at
+/// most one; `node` joins a key with its two finished subtrees.
+pub(crate) fn build_balanced<T>(
+    sorted: &[i64],
+    node: &impl Fn(i64, Option<T>, Option<T>) -> T,
+) -> Option<T> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let middle = sorted.len() / 2;
+    let left = build_balanced(&sorted[..middle], node);
+    let right = build_balanced(&sorted[middle + 1..], node);
+    Some(node(sorted[middle], left, right))
+}
+
+/// Comparison, hashing, collection and set-operator impls of an ordered set.
+///
+/// The set needs an `iter()` in comparator order, a `cmp: Comparator` field
+/// and a `with_sorted(&self, &[i64])` that builds a balanced set with the
+/// same comparator from keys already sorted by it.
+macro_rules! ordered_set_impls {
+    ($set:ident $(<$param:ident: $bound:path>)?, $iter:ident) => {
+        impl$(<$param: $bound>)? PartialEq for $set$(<$param>)? {
+            fn eq(&self, other: &Self) -> bool {
+                $crate::sorted_set::natural_keys(&self.cmp, self.iter())
+                    .eq($crate::sorted_set::natural_keys(&other.cmp, other.iter()))
+            }
+        }
+
+        impl$(<$param: $bound>)? Eq for $set$(<$param>)? {}
+
+        impl$(<$param: $bound>)? PartialOrd for $set$(<$param>)? {
+            fn partial_cmp(&self, other: &Self) -> Option<::std::cmp::Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+
+        impl$(<$param: $bound>)? Ord for $set$(<$param>)? {
+            fn cmp(&self, other: &Self) -> ::std::cmp::Ordering {
+                $crate::sorted_set::natural_keys(&self.cmp, self.iter())
+                    .cmp($crate::sorted_set::natural_keys(&other.cmp, other.iter()))
+            }
+        }
+
+        impl$(<$param: $bound>)? ::std::hash::Hash for $set$(<$param>)? {
+            fn hash<H: ::std::hash::Hasher>(&self, state: &mut H) {
+                let mut len = 0_usize;
+                for value in $crate::sorted_set::natural_keys(&self.cmp, self.iter()) {
+                    ::std::hash::Hash::hash(&value, state);
+                    len += 1;
+                }
+                state.write_usize(len);
+            }
+        }
+
+        impl$(<$param: $bound>)? FromIterator<i64> for $set$(<$param>)? {
+            fn from_iter<I: IntoIterator<Item = i64>>(iter: I) -> Self {
+                let mut set = Self::default();
+                set.extend(iter);
+                set
+            }
+        }
+
+        impl$(<$param: $bound>)? Extend<i64> for $set$(<$param>)? {
+            fn extend<I: IntoIterator<Item = i64>>(&mut self, iter: I) {
+                for value in iter {
+                    $crate::Tree::insert(self, value);
+                }
+            }
+        }
+
+        impl<'a, $($param: $bound)?> Extend<&'a i64> for $set$(<$param>)? {
+            fn extend<I: IntoIterator<Item = &'a i64>>(&mut self, iter: I) {
+                self.extend(iter.into_iter().copied());
+            }
+        }
+
+        impl<'a, $($param: $bound)?> IntoIterator for &'a $set$(<$param>)? {
+            type Item = &'a i64;
+            type IntoIter = $iter<'a, $($param)?>;
+
+            fn into_iter(self) -> Self::IntoIter {
+                self.iter()
+            }
+        }
+
+        impl$(<$param: $bound>)? $set$(<$param>)? {
+            // Merges the keys of both sets in the order of `self` and builds
+            // the result in O(n + m). Keys of `rhs` are sorted first if it
+            // uses another comparator.
+            fn merged(&self, op: $crate::sorted_set::SetOp, rhs: &Self) -> Self {
+                let rhs = $crate::sorted_set::in_order(&self.cmp, &rhs.cmp, rhs.iter());
+                self.with_sorted(&$crate::sorted_set::merge(
+                    op,
+                    &self.cmp,
+                    self.iter().copied(),
+                    rhs,
+                ))
+            }
+        }
+
+        impl$(<$param: $bound>)? ::std::ops::BitOr<&$set$(<$param>)?> for &$set$(<$param>)? {
+            type Output = $set$(<$param>)?;
+
+            /// Union ordered by the comparator of `self`.
+            fn bitor(self, rhs: &$set$(<$param>)?) -> Self::Output {
+                self.merged($crate::sorted_set::SetOp::Union, rhs)
+            }
+        }
+
+        impl$(<$param: $bound>)? ::std::ops::BitAnd<&$set$(<$param>)?> for &$set$(<$param>)? {
+            type Output = $set$(<$param>)?;
+
+            /// Intersection ordered by the comparator of `self`.
+            fn bitand(self, rhs: &$set$(<$param>)?) -> Self::Output {
+                self.merged($crate::sorted_set::SetOp::Intersection, rhs)
+            }
+        }
+
+        impl$(<$param: $bound>)? ::std::ops::Sub<&$set$(<$param>)?> for &$set$(<$param>)? {
+            type Output = $set$(<$param>)?;
+
+            /// Difference ordered by the comparator of `self`.
+            fn sub(self, rhs: &$set$(<$param>)?) -> Self::Output {
+                self.merged($crate::sorted_set::SetOp::Difference, rhs)
+            }
+        }
+    };
+}
+
+pub(crate) use ordered_set_impls;
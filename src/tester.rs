@@ -1,104 +1,689 @@
 use std::{
-    path::Path,
+    any::Any,
+    cmp::Ordering,
+    fmt,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{self, RecvTimeoutError},
+        Arc,
+    },
+    thread,
     time::{Duration, Instant},
 };
 
-pub fn run_test(path: impl AsRef<Path>, solver: impl Fn(Vec<&str>) -> String) {
-    run_test_lim(path, solver, u8::MAX);
+use serde::{Serialize, Serializer};
+
+// Solvers run on their own thread, and some recurse deeply (dropping a
+// degenerate BST does), so they get more than the 2 MiB default stack.
+const WORKER_STACK: usize = 256 << 20;
+
+// How much of the expected and actual output a result keeps.
+const EXCERPT_CHARS: usize = 80;
+
+/// How a single test ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Passed,
+    Failed,
+    /// The solver did not finish within the time limit.
+    Timeout,
+    Panicked,
+    /// There is no `.out` file to compare the answer with.
+    MissingOutput,
+    /// The input file cannot be read as UTF-8 text, so the solver never ran.
+    UnreadableInput,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Status::Passed => "passed",
+            Status::Failed => "FAILED",
+            Status::Timeout => "TIMEOUT",
+            Status::Panicked => "PANICKED",
+            Status::MissingOutput => "MISSING OUTPUT",
+            Status::UnreadableInput => "UNREADABLE INPUT",
+        })
+    }
+}
+
+fn nanos<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u64(duration.as_nanos() as u64)
+}
+
+/// One test of a run. `expected` and `output` hold the first
+/// characters of the expected answer and of the solver's answer.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TestResult {
+    pub name: String,
+    pub status: Status,
+    #[serde(rename = "duration_ns", serialize_with = "nanos")]
+    pub duration: Duration,
+    pub expected: String,
+    pub output: String,
 }
 
-pub fn run_silently(path: impl AsRef<Path>, solver: impl Fn(Vec<&str>) -> String) {
-    run_test_with_path(
-        path,
-        solver,
-        |n, t, expected, solved| {
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TestRunReport {
+    pub suite: String,
+    #[serde(rename = "elapsed_ns", serialize_with = "nanos")]
+    pub elapsed: Duration,
+    pub tests: Vec<TestResult>,
+}
+
+impl TestRunReport {
+    pub fn count(&self, status: Status) -> usize {
+        self.tests
+            .iter()
+            .filter(|test| test.status == status)
+            .count()
+    }
+
+    pub fn all_passed(&self) -> bool {
+        self.tests.iter().all(|test| test.status == Status::Passed)
+    }
+}
+
+/// Gets told about a run as it goes and about the whole report at the end.
+pub trait Reporter {
+    fn started(&mut self, _input: &Path) {}
+
+    fn finished(&mut self, _result: &TestResult) {}
+
+    fn completed(&mut self, report: &TestRunReport) -> io::Result<()>;
+}
+
+/// Human-readable progress on stdout. Verbose mode also tells where every
+/// test comes from and shows both answers of a failed test.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConsoleReporter {
+    pub verbose: bool,
+}
+
+impl Reporter for ConsoleReporter {
+    fn started(&mut self, input: &Path) {
+        if self.verbose {
+            println!("Start test at path: {input:?}");
+        }
+    }
+
+    fn finished(&mut self, result: &TestResult) {
+        println!(
+            "{} is {} in {:?}.",
+            result.name, result.status, result.duration
+        );
+        if self.verbose && result.status == Status::Failed {
             println!(
-                "Test {n} is {} in {t:?}.",
-                if expected == solved {
-                    "passed"
-                } else {
-                    "FAILED"
-                }
+                "Expected = '{}'\nSolved = {}",
+                result.expected, result.output
             );
-            true
-        },
-        final_report,
-    );
+        }
+    }
+
+    fn completed(&mut self, report: &TestRunReport) -> io::Result<()> {
+        println!("Have run {} tests.", report.tests.len());
+        println!("Success --- {}", report.count(Status::Passed));
+        for status in [
+            Status::Failed,
+            Status::Timeout,
+            Status::Panicked,
+            Status::MissingOutput,
+            Status::UnreadableInput,
+        ] {
+            let count = report.count(status);
+            if count > 0 {
+                println!("{status} --- {count}");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Writes the report as JSON.
+#[derive(Debug)]
+pub struct JsonReporter<W> {
+    writer: W,
+}
+
+impl<W: Write> JsonReporter<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+}
+
+impl JsonReporter<BufWriter<File>> {
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+impl<W: Write> Reporter for JsonReporter<W> {
+    fn completed(&mut self, report: &TestRunReport) -> io::Result<()> {
+        serde_json::to_writer_pretty(&mut self.writer, report)?;
+        writeln!(self.writer)?;
+        self.writer.flush()
+    }
+}
+
+/// Writes the report as a JUnit XML test suite. Wrong answers are failures;
+/// timeouts, panics and missing outputs are errors.
+#[derive(Debug)]
+pub struct JUnitReporter<W> {
+    writer: W,
+}
+
+impl<W: Write> JUnitReporter<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+}
+
+impl JUnitReporter<BufWriter<File>> {
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            ch => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+impl<W: Write> Reporter for JUnitReporter<W> {
+    fn completed(&mut self, report: &TestRunReport) -> io::Result<()> {
+        let suite = escape_xml(&report.suite);
+        let failures = report.count(Status::Failed);
+        let errors = report.tests.len() - failures - report.count(Status::Passed);
+        writeln!(self.writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(
+            self.writer,
+            r#"<testsuite name="{suite}" tests="{}" failures="{failures}" errors="{errors}" time="{:.6}">"#,
+            report.tests.len(),
+            report.elapsed.as_secs_f64()
+        )?;
+        for test in &report.tests {
+            write!(
+                self.writer,
+                r#"  <testcase name="{}" classname="{suite}" time="{:.6}""#,
+                escape_xml(&test.name),
+                test.duration.as_secs_f64()
+            )?;
+            let (element, message) = match test.status {
+                Status::Passed => {
+                    writeln!(self.writer, "/>")?;
+                    continue;
+                }
+                Status::Failed => ("failure", "wrong answer"),
+                Status::Timeout => ("error", "time limit exceeded"),
+                Status::Panicked => ("error", "solver panicked"),
+                Status::MissingOutput => ("error", "expected output is missing"),
+                Status::UnreadableInput => ("error", "input cannot be read"),
+            };
+            writeln!(
+                self.writer,
+                ">\n    <{element} message=\"{message}\">expected: {}\noutput: {}</{element}>\n  </testcase>",
+                escape_xml(&test.expected),
+                escape_xml(&test.output)
+            )?;
+        }
+        writeln!(self.writer, "</testsuite>")?;
+        self.writer.flush()
+    }
+}
+
+/// File names of a test suite. Each pattern has one `*`, which stands for
+/// the same id in the input and in the expected output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    input: String,
+    output: String,
+}
+
+impl Default for Pattern {
+    /// `test.*.in` and `test.*.out`.
+    fn default() -> Self {
+        Self {
+            input: "test.*.in".to_string(),
+            output: "test.*.out".to_string(),
+        }
+    }
+}
+
+impl Pattern {
+    pub fn new(input: &str, output: &str) -> Result<Self, String> {
+        for pattern in [input, output] {
+            if pattern.matches('*').count() != 1 {
+                return Err(format!("pattern {pattern:?} must have exactly one `*`"));
+            }
+        }
+        Ok(Self {
+            input: input.to_string(),
+            output: output.to_string(),
+        })
+    }
+
+    // The part of `file_name` that `*` matched.
+    fn id<'a>(&self, file_name: &'a str) -> Option<&'a str> {
+        let (prefix, suffix) = self.input.split_once('*').unwrap();
+        file_name
+            .strip_prefix(prefix)?
+            .strip_suffix(suffix)
+            .filter(|id| !id.is_empty())
+    }
 }
 
-pub fn run_test_lim<Solver>(path: impl AsRef<Path>, solver: Solver, max_test: u8)
+/// A test found on disk. The name is the input file name without what
+/// follows the `*` of the pattern, e.g. `test.7` for `test.7.in`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestCase {
+    pub name: String,
+    pub input: PathBuf,
+    pub output: PathBuf,
+}
+
+/// Every input in `path` that matches `pattern`, whether or not the numbers
+/// have gaps. Numeric ids come first in numeric order, then the rest
+/// alphabetically.
+pub fn discover(path: impl AsRef<Path>, pattern: &Pattern) -> io::Result<Vec<TestCase>> {
+    let path = path.as_ref();
+    let mut cases = vec![];
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let file_name = entry.file_name();
+        let Some(id) = file_name.to_str().and_then(|name| pattern.id(name)) else {
+            continue;
+        };
+        if !entry.file_type()?.is_file() {
+            continue;
+        }
+        let (prefix, _) = pattern.input.split_once('*').unwrap();
+        cases.push((
+            id.parse::<u64>().ok(),
+            id.to_string(),
+            TestCase {
+                name: format!("{prefix}{id}"),
+                input: entry.path(),
+                output: path.join(pattern.output.replace('*', id)),
+            },
+        ));
+    }
+    cases.sort_by(|(a, a_id, _), (b, b_id, _)| match (a, b) {
+        (Some(a), Some(b)) => a.cmp(b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => a_id.cmp(b_id),
+    });
+    Ok(cases.into_iter().map(|(_, _, case)| case).collect())
+}
+
+/// How to run a suite. The default runs every `test.*.in` without a time
+/// limit.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RunOptions {
+    pub pattern: Pattern,
+    /// A test that takes longer is marked `Timeout` and the run goes on.
+    pub time_limit: Option<Duration>,
+    /// Run only the first tests.
+    pub max_tests: Option<usize>,
+}
+
+pub fn run_test<Solver>(path: impl AsRef<Path>, solver: Solver) -> TestRunReport
 where
-    Solver: Fn(Vec<&str>) -> String,
+    Solver: Fn(Vec<&str>) -> String + Send + Sync + 'static,
 {
-    run_test_with_path(
-        path,
-        solver,
-        |n, t, expected, solved| {
-            report_one(n, t, expected, solved);
-            n < max_test
-        },
-        final_report,
-    );
-}
-
-pub fn run_test_with_path(
+    run_on_console(path, solver, RunOptions::default(), true)
+}
+
+/// `run_test` that gives up on a test after `time_limit` and moves on to the
+/// next one.
+pub fn run_test_with_limit<Solver>(
     path: impl AsRef<Path>,
-    solver: impl Fn(Vec<&str>) -> String,
-    ok_to_proceed: impl Fn(u8, Duration, &str, &str) -> bool,
-    report: impl FnOnce(i32, i32),
-) {
-    let mut test_number = 0_u8;
-    let mut ok = 0;
-    let mut failed = 0;
-    while let Ok(str_n) =
-        std::fs::read_to_string(path.as_ref().join(format!("test.{test_number}.in")))
-    {
-        println!(
-            "Start test at path: {:?}",
-            path.as_ref().join(format!("test.{test_number}.in"))
+    solver: Solver,
+    time_limit: Duration,
+) -> TestRunReport
+where
+    Solver: Fn(Vec<&str>) -> String + Send + Sync + 'static,
+{
+    let options = RunOptions {
+        time_limit: Some(time_limit),
+        ..RunOptions::default()
+    };
+    run_on_console(path, solver, options, true)
+}
+
+pub fn run_silently<Solver>(path: impl AsRef<Path>, solver: Solver) -> TestRunReport
+where
+    Solver: Fn(Vec<&str>) -> String + Send + Sync + 'static,
+{
+    run_on_console(path, solver, RunOptions::default(), false)
+}
+
+/// `run_test` on the first `max_tests` tests only.
+pub fn run_test_lim<Solver>(
+    path: impl AsRef<Path>,
+    solver: Solver,
+    max_tests: usize,
+) -> TestRunReport
+where
+    Solver: Fn(Vec<&str>) -> String + Send + Sync + 'static,
+{
+    let options = RunOptions {
+        max_tests: Some(max_tests),
+        ..RunOptions::default()
+    };
+    run_on_console(path, solver, options, true)
+}
+
+// Reports a suite that cannot be read on stderr and returns it empty.
+fn run_on_console<Solver>(
+    path: impl AsRef<Path>,
+    solver: Solver,
+    options: RunOptions,
+    verbose: bool,
+) -> TestRunReport
+where
+    Solver: Fn(Vec<&str>) -> String + Send + Sync + 'static,
+{
+    let path = path.as_ref();
+    let mut console = ConsoleReporter { verbose };
+    run_test_with_path(path, solver, &options, &mut [&mut console]).unwrap_or_else(|error| {
+        eprintln!("Cannot run the tests in {path:?}: {error}");
+        TestRunReport {
+            suite: path.display().to_string(),
+            elapsed: Duration::ZERO,
+            tests: vec![],
+        }
+    })
+}
+
+/// Runs the tests that `options.pattern` finds in `path` and compares the
+/// answers with the expected outputs. A missing output file or an input
+/// that cannot be read does not stop the run: the test is reported as
+/// `MissingOutput` or `UnreadableInput`, the latter with the error as its
+/// output.
+///
+/// The solver runs on a worker thread, and a panic in it is reported as
+/// `Panicked` for that test only. With a time limit a test that takes
+/// longer is marked `Timeout`. There is no way to stop a thread, so a hung
+/// solver keeps running in the background until it finishes or the process
+/// exits.
+///
+/// Every reporter hears about every test. Fails if the directory cannot be
+/// read, or if a reporter fails.
+pub fn run_test_with_path<Solver>(
+    path: impl AsRef<Path>,
+    solver: Solver,
+    options: &RunOptions,
+    reporters: &mut [&mut dyn Reporter],
+) -> io::Result<TestRunReport>
+where
+    Solver: Fn(Vec<&str>) -> String + Send + Sync + 'static,
+{
+    let path = path.as_ref();
+    let solver = Arc::new(solver);
+    let start = Instant::now();
+    let mut cases = discover(path, &options.pattern)?;
+    cases.truncate(options.max_tests.unwrap_or(usize::MAX));
+    let mut tests = vec![];
+    for case in cases {
+        for reporter in reporters.iter_mut() {
+            reporter.started(&case.input);
+        }
+        let outcome =
+            fs::read_to_string(&case.input).map(|input| solve(&solver, input, options.time_limit));
+        let expected = fs::read_to_string(&case.output).ok();
+        let expected = expected.as_deref().map(str::trim);
+        let (status, duration, output) = match outcome {
+            Err(error) => (Status::UnreadableInput, Duration::ZERO, error.to_string()),
+            Ok(Outcome::TimedOut) => (Status::Timeout, options.time_limit.unwrap(), String::new()),
+            Ok(Outcome::Panicked(message, elapsed)) => (Status::Panicked, elapsed, message),
+            Ok(Outcome::Solved(solved, elapsed)) => match expected {
+                None => (Status::MissingOutput, elapsed, solved),
+                Some(expected) if expected == solved => (Status::Passed, elapsed, solved),
+                Some(_) => (Status::Failed, elapsed, solved),
+            },
+        };
+        let result = TestResult {
+            name: case.name,
+            status,
+            duration,
+            expected: expected
+                .unwrap_or_default()
+                .chars()
+                .take(EXCERPT_CHARS)
+                .collect(),
+            output: output.chars().take(EXCERPT_CHARS).collect(),
+        };
+        for reporter in reporters.iter_mut() {
+            reporter.finished(&result);
+        }
+        tests.push(result);
+    }
+    let report = TestRunReport {
+        suite: path.display().to_string(),
+        elapsed: start.elapsed(),
+        tests,
+    };
+    for reporter in reporters.iter_mut() {
+        reporter.completed(&report)?;
+    }
+    Ok(report)
+}
+
+enum Outcome {
+    Solved(String, Duration),
+    /// The panic message.
+    Panicked(String, Duration),
+    TimedOut,
+}
+
+fn panic_message(panic: &(dyn Any + Send)) -> String {
+    panic
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "panic with a non-string payload".to_string())
+}
+
+fn solve<Solver>(solver: &Arc<Solver>, input: String, time_limit: Option<Duration>) -> Outcome
+where
+    Solver: Fn(Vec<&str>) -> String + Send + Sync + 'static,
+{
+    let solver = Arc::clone(solver);
+    let (sender, receiver) = mpsc::channel();
+    let worker = thread::Builder::new()
+        .name("solver".to_string())
+        .stack_size(WORKER_STACK)
+        .spawn(move || {
+            let start = Instant::now();
+            let solved = panic::catch_unwind(AssertUnwindSafe(|| solver(input.lines().collect())));
+            let elapsed = Instant::now().duration_since(start);
+            // Nobody listens any more if the test has timed out.
+            let _ = sender.send(match solved {
+                Ok(solved) => Outcome::Solved(solved, elapsed),
+                Err(panic) => Outcome::Panicked(panic_message(panic.as_ref()), elapsed),
+            });
+        })
+        .expect("cannot spawn a solver thread");
+    let outcome = match time_limit {
+        Some(limit) => receiver.recv_timeout(limit),
+        None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+    };
+    match outcome {
+        Ok(outcome) => {
+            worker.join().unwrap();
+            outcome
+        }
+        Err(RecvTimeoutError::Timeout) => Outcome::TimedOut,
+        Err(RecvTimeoutError::Disconnected) => {
+            unreachable!("the solver thread always sends an outcome")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use super::*;
+
+    // Records what it is told.
+    #[derive(Default)]
+    struct Collect {
+        finished: Vec<(String, Status)>,
+        completed: Option<TestRunReport>,
+    }
+
+    impl Reporter for Collect {
+        fn finished(&mut self, result: &TestResult) {
+            self.finished.push((result.name.clone(), result.status));
+        }
+
+        fn completed(&mut self, report: &TestRunReport) -> io::Result<()> {
+            self.completed = Some(report.clone());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_time_limit_and_reporters() {
+        let dir = env::temp_dir().join(format!("tester_time_limit_{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for (n, input, output) in [(0, "1", "1"), (1, "hang", "hang"), (2, "2 <&>", "3")] {
+            fs::write(dir.join(format!("test.{n}.in")), input).unwrap();
+            fs::write(dir.join(format!("test.{n}.out")), output).unwrap();
+        }
+        let mut collect = Collect::default();
+        let mut json = JsonReporter::new(vec![]);
+        let mut junit = JUnitReporter::new(vec![]);
+        let report = run_test_with_path(
+            &dir,
+            |data| {
+                if data[0] == "hang" {
+                    thread::sleep(Duration::from_secs(10));
+                }
+                data[0].to_string()
+            },
+            &RunOptions {
+                time_limit: Some(Duration::from_millis(200)),
+                ..RunOptions::default()
+            },
+            &mut [&mut collect, &mut json, &mut junit],
+        )
+        .unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            vec![
+                ("test.0".to_string(), Status::Passed),
+                ("test.1".to_string(), Status::Timeout),
+                ("test.2".to_string(), Status::Failed)
+            ],
+            collect.finished
         );
-        let input_data: Vec<&str> = str_n.lines().collect();
-        let start = Instant::now();
-        let solved = solver(input_data);
-        let elapsed = Instant::now().duration_since(start);
-        let result_path = path.as_ref().join(format!("test.{test_number}.out"));
-        println!("Test finished. Compare with result at path {result_path:?}");
-        let expected_result = std::fs::read_to_string(result_path).unwrap();
-        let expected_result = expected_result.trim();
-        if expected_result.trim() == solved {
-            ok += 1;
-        } else {
-            failed += 1;
-        }
-        if ok_to_proceed(test_number, elapsed, expected_result, &solved) {
-            test_number += 1;
-        } else {
-            break;
-        }
-    }
-    report(ok, failed);
-}
-
-fn final_report(ok: i32, failed: i32) {
-    let complete = ok + failed;
-    println!("Have run {complete} tests.");
-    println!("Success --- {ok}");
-    if failed > 0 {
-        println!("FAILED --- {failed}");
-    }
-}
-
-fn report_one(n: u8, t: Duration, expected: &str, solved: &str) {
-    let passed = expected == solved;
-    println!(
-        "Test {n} is {} in {t:?}.",
-        if passed { "passed" } else { "FAILED" }
-    );
-    if !passed {
-        println!(
-            "Expected = '{}'\nSolved = {}",
-            expected.chars().take(80).collect::<String>(),
-            solved.chars().take(80).collect::<String>(),
+        assert_eq!(Some(&report), collect.completed.as_ref());
+        assert_eq!(Duration::from_millis(200), report.tests[1].duration);
+        assert_eq!("2 <&>", report.tests[2].output);
+        assert_eq!(
+            [1, 1, 1],
+            [Status::Passed, Status::Failed, Status::Timeout].map(|status| report.count(status))
+        );
+        assert!(!report.all_passed());
+
+        let json: serde_json::Value = serde_json::from_slice(&json.writer).unwrap();
+        assert_eq!("timeout", json["tests"][1]["status"]);
+        assert_eq!(200_000_000, json["tests"][1]["duration_ns"]);
+
+        let junit = String::from_utf8(junit.writer).unwrap();
+        assert!(junit.contains(r#"tests="3" failures="1" errors="1""#));
+        assert!(junit.contains(r#"<testcase name="test.0""#));
+        assert!(junit.contains(r#"<error message="time limit exceeded">"#));
+        assert!(junit.contains("output: 2 &lt;&amp;&gt;</failure>"));
+    }
+
+    #[test]
+    fn test_discovery_panics_and_missing_output() {
+        let dir = env::temp_dir().join(format!("tester_discovery_{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        // Gaps, more than 255 tests and a test without its output.
+        for n in (0..300).filter(|n| n % 7 != 3) {
+            fs::write(dir.join(format!("test.{n}.in")), n.to_string()).unwrap();
+            if n != 100 {
+                fs::write(dir.join(format!("test.{n}.out")), n.to_string()).unwrap();
+            }
+        }
+        fs::write(dir.join("test.big.in"), "big").unwrap();
+        fs::write(dir.join("test.big.out"), "big").unwrap();
+        fs::write(dir.join("test.bad.in"), [0xff, 0xfe]).unwrap();
+        fs::write(dir.join("test.bad.out"), "bad").unwrap();
+        fs::write(dir.join("notes.txt"), "").unwrap();
+        fs::write(dir.join("case-a.txt"), "7").unwrap();
+        fs::write(dir.join("case-a.ans"), "7").unwrap();
+
+        let cases = discover(&dir, &Pattern::default()).unwrap();
+        assert_eq!(259, cases.len());
+        assert_eq!(
+            ["test.0", "test.1", "test.2", "test.4"],
+            [0, 1, 2, 3].map(|index| cases[index].name.as_str())
         );
+        assert_eq!("test.299", cases[256].name);
+        assert_eq!("test.bad", cases[257].name);
+        assert_eq!("test.big", cases[258].name);
+        assert_eq!(dir.join("test.big.out"), cases[258].output);
+
+        let solver = |data: Vec<&str>| {
+            if data[0] == "200" {
+                panic!("cannot solve {}", data[0]);
+            }
+            data[0].to_string()
+        };
+        let report = run_test_with_path(&dir, solver, &RunOptions::default(), &mut []).unwrap();
+        assert_eq!(259, report.tests.len());
+        assert_eq!(256, report.count(Status::Passed));
+        assert_eq!(Status::UnreadableInput, report.tests[257].status);
+        assert!(!report.tests[257].output.is_empty());
+        let missing = report
+            .tests
+            .iter()
+            .find(|test| test.name == "test.100")
+            .unwrap();
+        assert_eq!(Status::MissingOutput, missing.status);
+        assert_eq!("100", missing.output);
+        let panicked = report
+            .tests
+            .iter()
+            .find(|test| test.status == Status::Panicked)
+            .unwrap();
+        assert_eq!("test.200", panicked.name);
+        assert_eq!("cannot solve 200", panicked.output);
+
+        let options = RunOptions {
+            pattern: Pattern::new("case-*.txt", "case-*.ans").unwrap(),
+            max_tests: Some(5),
+            ..RunOptions::default()
+        };
+        let report = run_test_with_path(&dir, solver, &options, &mut []).unwrap();
+        assert_eq!(1, report.tests.len());
+        assert_eq!("case-a", report.tests[0].name);
+        assert!(report.all_passed());
+        assert_eq!(3, run_test_lim(&dir, solver, 3).tests.len());
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(Pattern::new("test.in", "test.*.out").is_err());
+        assert!(Pattern::new("*.*.in", "*.out").is_err());
     }
 }
@@ -1,55 +1,133 @@
-use std::cmp::Ordering;
+use std::{
+    cmp::Ordering,
+    fmt::{self, Debug, Formatter},
+    mem::swap,
+    ops::{Bound, RangeBounds},
+    vec,
+};
 
 use rand::Rng;
 
-use crate::Tree;
-type Treap = Option<Box<Node>>;
+use crate::{
+    comparator::Comparator,
+    cursor::{self, BinaryNode, Cursor, CursorMut, CursorSet},
+    link::{Link, Owned},
+    memory::HeapSize,
+    monoid::{after_start, before_end, Monoid},
+    sorted_set::{build_balanced, ordered_set_impls},
+    viz::{priority_label, Shape},
+    Tree,
+};
+pub(crate) type Treap<M, K = i64, F = Owned> = Option<<F as Link>::Ptr<Node<M, K, F>>>;
 
-#[derive(Debug, Clone, PartialEq)]
-struct Node {
-    value: i64,
-    priority: usize,
-    left: Treap,
-    right: Treap,
+pub(crate) struct Node<M: Monoid<K>, K = i64, F: Link = Owned> {
+    pub(crate) value: K,
+    pub(crate) priority: usize,
+    pub(crate) summary: M::Summary,
+    pub(crate) left: Treap<M, K, F>,
+    pub(crate) right: Treap<M, K, F>,
+}
+
+impl<M: Monoid<K>, K: Copy, F: Link> Clone for Node<M, K, F> {
+    fn clone(&self) -> Self {
+        Self {
+            value: self.value,
+            priority: self.priority,
+            summary: self.summary.clone(),
+            left: self.left.as_ref().map(F::clone_ptr),
+            right: self.right.as_ref().map(F::clone_ptr),
+        }
+    }
 }
 
-impl Node {
-    pub fn new(value: i64) -> Self {
+impl<M: Monoid<K>, K: Debug, F: Link> Debug for Node<M, K, F> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Node")
+            .field("value", &self.value)
+            .field("priority", &self.priority)
+            .field("summary", &self.summary)
+            .field("left", &self.left.as_deref())
+            .field("right", &self.right.as_deref())
+            .finish()
+    }
+}
+
+impl<M: Monoid<K>, K: PartialEq, F: Link> PartialEq for Node<M, K, F> {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+            && self.priority == other.priority
+            && self.summary == other.summary
+            && self.left.as_deref() == other.left.as_deref()
+            && self.right.as_deref() == other.right.as_deref()
+    }
+}
+
+impl<M: Monoid<K>, K: Copy, F: Link> Node<M, K, F> {
+    pub fn new(value: K) -> Self {
         let mut rng = rand::thread_rng();
         Self {
             value,
             priority: rng.gen(),
+            summary: M::lift(value),
             left: None,
             right: None,
         }
     }
+
+    pub fn update_summary(&mut self) {
+        self.summary = M::combine(
+            &M::combine(&summary::<M, K, F>(&self.left), &M::lift(self.value)),
+            &summary::<M, K, F>(&self.right),
+        );
+    }
+}
+
+fn summary<M: Monoid<K>, K, F: Link>(t: &Treap<M, K, F>) -> M::Summary {
+    t.as_ref()
+        .map_or_else(M::identity, |node| node.summary.clone())
 }
 
-fn merge(t1: Treap, t2: Treap) -> Treap {
+// The functions below go through `Link::make_mut`, so on shared nodes they
+// copy the path they touch and leave other versions intact.
+pub(crate) fn merge<M: Monoid<K>, K: Copy, F: Link>(
+    t1: Treap<M, K, F>,
+    t2: Treap<M, K, F>,
+) -> Treap<M, K, F> {
     match (t1, t2) {
         (None, t) | (t, None) => t,
         (Some(mut left), Some(mut right)) => {
             if left.priority > right.priority {
-                left.right = merge(left.right, Some(right));
+                let node = F::make_mut(&mut left);
+                node.right = merge::<M, K, F>(node.right.take(), Some(right));
+                node.update_summary();
                 Some(left)
             } else {
-                right.left = merge(Some(left), right.left);
+                let node = F::make_mut(&mut right);
+                node.left = merge::<M, K, F>(Some(left), node.left.take());
+                node.update_summary();
                 Some(right)
             }
         }
     }
 }
 
-fn split(t: Treap, value: i64) -> (Treap, Treap) {
+// Nodes for which `goes_left` holds must form a prefix of the key order.
+pub(crate) fn split_by<M: Monoid<K>, K: Copy, F: Link>(
+    t: Treap<M, K, F>,
+    goes_left: &impl Fn(&K) -> bool,
+) -> (Treap<M, K, F>, Treap<M, K, F>) {
     match t {
         Some(mut root) => {
-            if root.value <= value {
-                let (left, right) = split(root.right, value);
-                root.right = left;
+            let node = F::make_mut(&mut root);
+            if goes_left(&node.value) {
+                let (left, right) = split_by::<M, K, F>(node.right.take(), goes_left);
+                node.right = left;
+                node.update_summary();
                 (Some(root), right)
             } else {
-                let (left, right) = split(root.left, value);
-                root.left = right;
+                let (left, right) = split_by::<M, K, F>(node.left.take(), goes_left);
+                node.left = right;
+                node.update_summary();
                 (left, Some(root))
             }
         }
@@ -57,42 +135,114 @@ fn split(t: Treap, value: i64) -> (Treap, Treap) {
     }
 }
 
-fn insert(value: i64, t: Treap) -> Treap {
-    let node = Some(Box::new(Node::new(value)));
-    let (left, right) = split(t, value);
-    merge(merge(left, node), right)
+pub(crate) fn split<M: Monoid, F: Link>(
+    cmp: &Comparator,
+    t: Treap<M, i64, F>,
+    value: i64,
+) -> (Treap<M, i64, F>, Treap<M, i64, F>) {
+    split_by::<M, i64, F>(t, &|key| cmp.compare(key, &value) != Ordering::Greater)
+}
+
+pub(crate) fn insert<M: Monoid, F: Link>(
+    cmp: &Comparator,
+    value: i64,
+    t: Treap<M, i64, F>,
+) -> Treap<M, i64, F> {
+    let node = Some(F::new(Node::new(value)));
+    let (left, right) = split::<M, F>(cmp, t, value);
+    merge::<M, i64, F>(merge::<M, i64, F>(left, node), right)
 }
 
-fn remove(value: i64, t: Treap) -> Treap {
+pub(crate) fn remove<M: Monoid, F: Link>(
+    cmp: &Comparator,
+    value: i64,
+    t: Treap<M, i64, F>,
+) -> Treap<M, i64, F> {
     let mut root = t?;
-    match root.value.cmp(&value) {
-        Ordering::Less => root.right = remove(value, root.right),
-        Ordering::Equal => return merge(root.left, root.right),
-        Ordering::Greater => root.left = remove(value, root.left),
+    let node = F::make_mut(&mut root);
+    match cmp.compare(&node.value, &value) {
+        Ordering::Less => node.right = remove::<M, F>(cmp, value, node.right.take()),
+        Ordering::Equal => return merge::<M, i64, F>(node.left.take(), node.right.take()),
+        Ordering::Greater => node.left = remove::<M, F>(cmp, value, node.left.take()),
     }
+    node.update_summary();
     Some(root)
 }
 
-#[derive(Debug, Default, Clone, PartialEq)]
-pub struct TreapSet {
-    root: Treap,
+// Same walk as `fold` in `avl.rs`.
+fn fold<M: Monoid>(cmp: &Comparator, t: &Treap<M>, range: &impl RangeBounds<i64>) -> M::Summary {
+    let node = match t {
+        None => return M::identity(),
+        Some(node) => node,
+    };
+    let (start, end) = (range.start_bound(), range.end_bound());
+    if !after_start(cmp, start, node.value) {
+        return fold(cmp, &node.right, range);
+    }
+    if !before_end(cmp, end, node.value) {
+        return fold(cmp, &node.left, range);
+    }
+    let left = match start {
+        Bound::Unbounded => summary::<M, i64, Owned>(&node.left),
+        _ => fold(cmp, &node.left, &(start, Bound::Unbounded)),
+    };
+    let right = match end {
+        Bound::Unbounded => summary::<M, i64, Owned>(&node.right),
+        _ => fold(cmp, &node.right, &(Bound::Unbounded, end)),
+    };
+    M::combine(&M::combine(&left, &M::lift(node.value)), &right)
+}
+
+/// Treap that keeps a `M` summary of every subtree.
+#[derive(Debug, Clone)]
+pub struct AugmentedTreapSet<M: Monoid> {
+    root: Treap<M>,
+    cmp: Comparator,
 }
 
-impl Tree for TreapSet {
+pub type TreapSet = AugmentedTreapSet<()>;
+
+impl<M: Monoid> Default for AugmentedTreapSet<M> {
+    fn default() -> Self {
+        Self {
+            root: None,
+            cmp: Comparator::default(),
+        }
+    }
+}
+
+pub(crate) fn shape<M: Monoid, F: Link>(tree: &Treap<M, i64, F>) -> Option<Shape> {
+    tree.as_ref().map(|node| {
+        Shape::new(
+            node.value,
+            priority_label(node.value.to_string(), node.priority),
+            shape::<M, F>(&node.left),
+            shape::<M, F>(&node.right),
+        )
+    })
+}
+
+impl<M: Monoid> Tree for AugmentedTreapSet<M> {
     fn insert(&mut self, value: i64) -> bool {
-        self.root = insert(value, self.root.take());
+        if self.search(value) {
+            return false;
+        }
+        self.root = insert::<M, Owned>(&self.cmp, value, self.root.take());
         true
     }
 
     fn remove(&mut self, value: i64) -> Option<i64> {
-        self.root = remove(value, self.root.take());
+        if !self.search(value) {
+            return None;
+        }
+        self.root = remove::<M, Owned>(&self.cmp, value, self.root.take());
         Some(value)
     }
 
     fn search(&self, value: i64) -> bool {
         let mut current = &self.root;
         while let Some(node) = current {
-            match node.value.cmp(&value) {
+            match self.cmp.compare(&node.value, &value) {
                 Ordering::Less => current = &node.right,
                 Ordering::Equal => return true,
                 Ordering::Greater => current = &node.left,
@@ -100,24 +250,51 @@ impl Tree for TreapSet {
         }
         false
     }
+
+    fn values(&self) -> Vec<i64> {
+        self.iter().copied().collect()
+    }
+
+    fn shape(&self) -> Option<Shape> {
+        shape::<M, Owned>(&self.root)
+    }
 }
 
-impl TreapSet {
-    pub fn iter(&self) -> TreapIter {
+impl<M: Monoid> AugmentedTreapSet<M> {
+    pub fn with_comparator(
+        compare: impl Fn(&i64, &i64) -> Ordering + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            root: None,
+            cmp: Comparator::new(compare),
+        }
+    }
+
+    pub fn iter(&self) -> TreapIter<'_, M> {
         TreapIter {
             prev_nodes: vec![],
             current: &self.root,
         }
     }
+
+    /// Summary of the whole tree.
+    pub fn summary(&self) -> M::Summary {
+        summary::<M, i64, Owned>(&self.root)
+    }
+
+    /// Summary of the keys inside `range` in O(log n).
+    pub fn fold(&self, range: impl RangeBounds<i64>) -> M::Summary {
+        fold(&self.cmp, &self.root, &range)
+    }
 }
 
 #[derive(Debug)]
-pub struct TreapIter<'a> {
-    prev_nodes: Vec<&'a Node>,
-    current: &'a Treap,
+pub struct TreapIter<'a, M: Monoid> {
+    prev_nodes: Vec<&'a Node<M>>,
+    current: &'a Treap<M>,
 }
 
-impl<'a> Iterator for TreapIter<'a> {
+impl<'a, M: Monoid> Iterator for TreapIter<'a, M> {
     type Item = &'a i64;
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -147,3 +324,451 @@ impl<'a> Iterator for TreapIter<'a> {
         }
     }
 }
+
+impl<M: Monoid> AugmentedTreapSet<M> {
+    pub fn drain(&mut self) -> TreapIntoIter<M> {
+        TreapIntoIter {
+            prev_nodes: vec![],
+            current: self.root.take(),
+        }
+    }
+
+    /// Keeps the values matching `f`. The treap is rebuilt in O(n).
+    pub fn retain(&mut self, mut f: impl FnMut(&i64) -> bool) {
+        let kept = self.drain().filter(|value| f(value)).collect::<Vec<i64>>();
+        self.root = build_balanced(&kept, &Node::join);
+    }
+
+    /// Removes the values matching `pred` right away and returns them in order.
+    /// The treap is rebuilt in O(n).
+    pub fn extract_if(&mut self, mut pred: impl FnMut(&i64) -> bool) -> vec::IntoIter<i64> {
+        let (extracted, kept): (Vec<i64>, Vec<i64>) = self.drain().partition(|value| pred(value));
+        self.root = build_balanced(&kept, &Node::join);
+        extracted.into_iter()
+    }
+
+    fn with_sorted(&self, sorted: &[i64]) -> Self {
+        Self {
+            root: build_balanced(sorted, &Node::join),
+            cmp: self.cmp.clone(),
+        }
+    }
+}
+
+impl<M: Monoid> Node<M> {
+    // Joins the subtrees under a fresh node and sifts its random priority
+    // down into heap order, so a bulk build runs in O(n) like building a
+    // binary heap.
+    fn join(value: i64, left: Treap<M>, right: Treap<M>) -> Box<Self> {
+        let mut node = Box::new(Self::new(value));
+        node.left = left;
+        node.right = right;
+        node.update_summary();
+        sift_down(&mut node);
+        node
+    }
+}
+
+fn sift_down<M: Monoid>(node: &mut Node<M>) {
+    let mut current = node;
+    loop {
+        let child = match (current.left.as_deref_mut(), current.right.as_deref_mut()) {
+            (Some(left), Some(right)) if left.priority < right.priority => right,
+            (Some(left), _) => left,
+            (None, Some(right)) => right,
+            (None, None) => return,
+        };
+        if child.priority <= current.priority {
+            return;
+        }
+        swap(&mut child.priority, &mut current.priority);
+        current = child;
+    }
+}
+
+#[cfg(feature = "rayon")]
+mod par {
+    use rayon::iter::{FromParallelIterator, IntoParallelIterator, ParallelIterator};
+
+    use crate::parallel::{self, sorted_unique};
+
+    use super::*;
+
+    // Like `split`, but the node equal to `value` is cut out and reported.
+    fn split_out<M: Monoid>(
+        cmp: &Comparator,
+        t: Treap<M>,
+        value: i64,
+    ) -> (Treap<M>, bool, Treap<M>) {
+        let mut root = match t {
+            Some(root) => root,
+            None => return (None, false, None),
+        };
+        match cmp.compare(&root.value, &value) {
+            Ordering::Less => {
+                let (left, found, right) = split_out(cmp, root.right.take(), value);
+                root.right = left;
+                root.update_summary();
+                (Some(root), found, right)
+            }
+            Ordering::Equal => (root.left.take(), true, root.right.take()),
+            Ordering::Greater => {
+                let (left, found, right) = split_out(cmp, root.left.take(), value);
+                root.left = right;
+                root.update_summary();
+                (left, found, Some(root))
+            }
+        }
+    }
+
+    // The root with the higher priority stays on top and splits the other
+    // treap; both halves are then merged independently.
+    fn union<M: Monoid>(cmp: &Comparator, depth: usize, t1: Treap<M>, t2: Treap<M>) -> Treap<M>
+    where
+        M::Summary: Send,
+    {
+        match (t1, t2) {
+            (None, t) | (t, None) => t,
+            (Some(mut root), Some(mut other)) => {
+                if root.priority < other.priority {
+                    swap(&mut root, &mut other);
+                }
+                let (left, _, right) = split_out(cmp, Some(other), root.value);
+                let (root_left, root_right) = (root.left.take(), root.right.take());
+                let (left, right) = parallel::join(
+                    depth,
+                    || union(cmp, depth + 1, root_left, left),
+                    || union(cmp, depth + 1, root_right, right),
+                );
+                root.left = left;
+                root.right = right;
+                root.update_summary();
+                Some(root)
+            }
+        }
+    }
+
+    fn intersection<M: Monoid>(
+        cmp: &Comparator,
+        depth: usize,
+        t1: Treap<M>,
+        t2: Treap<M>,
+    ) -> Treap<M>
+    where
+        M::Summary: Send,
+    {
+        let (mut root, other) = match (t1, t2) {
+            (Some(root), other @ Some(_)) => (root, other),
+            _ => return None,
+        };
+        let (left, found, right) = split_out(cmp, other, root.value);
+        let (root_left, root_right) = (root.left.take(), root.right.take());
+        let (left, right) = parallel::join(
+            depth,
+            || intersection(cmp, depth + 1, root_left, left),
+            || intersection(cmp, depth + 1, root_right, right),
+        );
+        if !found {
+            return merge::<M, i64, Owned>(left, right);
+        }
+        root.left = left;
+        root.right = right;
+        root.update_summary();
+        Some(root)
+    }
+
+    fn difference<M: Monoid>(cmp: &Comparator, depth: usize, t1: Treap<M>, t2: Treap<M>) -> Treap<M>
+    where
+        M::Summary: Send,
+    {
+        let (mut root, other) = match (t1, t2) {
+            (None, _) => return None,
+            (t, None) => return t,
+            (Some(root), other) => (root, other),
+        };
+        let (left, found, right) = split_out(cmp, other, root.value);
+        let (root_left, root_right) = (root.left.take(), root.right.take());
+        let (left, right) = parallel::join(
+            depth,
+            || difference(cmp, depth + 1, root_left, left),
+            || difference(cmp, depth + 1, root_right, right),
+        );
+        if found {
+            return merge::<M, i64, Owned>(left, right);
+        }
+        root.left = left;
+        root.right = right;
+        root.update_summary();
+        Some(root)
+    }
+
+    /// Bulk operations that split the work between the threads of the rayon
+    /// pool. The result keeps the comparator of `self`. Splitting needs both
+    /// sets to order keys the same way, so if `other` uses another comparator
+    /// they fall back to the serial operators, which re-sort it.
+    impl<M: Monoid> AugmentedTreapSet<M>
+    where
+        M::Summary: Send,
+    {
+        pub fn par_union(mut self, other: Self) -> Self {
+            if self.cmp != other.cmp {
+                return &self | &other;
+            }
+            self.root = union(&self.cmp, 0, self.root.take(), other.root);
+            self
+        }
+
+        pub fn par_intersection(mut self, other: Self) -> Self {
+            if self.cmp != other.cmp {
+                return &self & &other;
+            }
+            self.root = intersection(&self.cmp, 0, self.root.take(), other.root);
+            self
+        }
+
+        pub fn par_difference(mut self, other: Self) -> Self {
+            if self.cmp != other.cmp {
+                return &self - &other;
+            }
+            self.root = difference(&self.cmp, 0, self.root.take(), other.root);
+            self
+        }
+    }
+
+    /// Sorts the values in parallel and builds the treap in O(n).
+    impl<M: Monoid> FromParallelIterator<i64> for AugmentedTreapSet<M>
+    where
+        M::Summary: Send,
+    {
+        fn from_par_iter<I: IntoParallelIterator<Item = i64>>(values: I) -> Self {
+            let mut set = Self::default();
+            let sorted = sorted_unique(&set.cmp, values.into_par_iter().collect());
+            set.root = parallel::build_balanced(0, &sorted, &Node::join);
+            set
+        }
+    }
+}
+
+impl<M: Monoid> AugmentedTreapSet<M> {
+    /// Smallest value, in the order of the comparator.
+    pub fn first(&self) -> Option<&i64> {
+        cursor::first(self.root())
+    }
+
+    /// Largest value, in the order of the comparator.
+    pub fn last(&self) -> Option<&i64> {
+        cursor::last(self.root())
+    }
+
+    /// Cursor at the ghost position between the last and the first value.
+    pub fn cursor(&self) -> Cursor<'_> {
+        Cursor::new(self)
+    }
+
+    pub fn cursor_mut(&mut self) -> CursorMut<'_> {
+        CursorMut::new(self)
+    }
+}
+
+impl<M: Monoid> BinaryNode for Node<M> {
+    fn value(&self) -> &i64 {
+        &self.value
+    }
+
+    fn left(&self) -> Option<&dyn BinaryNode> {
+        self.left.as_deref().map(|node| node as &dyn BinaryNode)
+    }
+
+    fn right(&self) -> Option<&dyn BinaryNode> {
+        self.right.as_deref().map(|node| node as &dyn BinaryNode)
+    }
+}
+
+impl<M: Monoid> CursorSet for AugmentedTreapSet<M> {
+    fn root(&self) -> Option<&dyn BinaryNode> {
+        self.root.as_deref().map(|node| node as &dyn BinaryNode)
+    }
+
+    fn comparator(&self) -> &Comparator {
+        &self.cmp
+    }
+}
+
+ordered_set_impls!(AugmentedTreapSet<M: Monoid>, TreapIter);
+
+impl<M: Monoid> IntoIterator for AugmentedTreapSet<M> {
+    type Item = i64;
+    type IntoIter = TreapIntoIter<M>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        TreapIntoIter {
+            prev_nodes: vec![],
+            current: self.root,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct TreapIntoIter<M: Monoid> {
+    prev_nodes: Vec<Node<M>>,
+    current: Treap<M>,
+}
+
+impl<M: Monoid> Iterator for TreapIntoIter<M> {
+    type Item = i64;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(mut node) = self.current.take() {
+            self.current = node.left.take();
+            self.prev_nodes.push(*node);
+        }
+        let mut node = self.prev_nodes.pop()?;
+        self.current = node.right.take();
+        Some(node.value)
+    }
+}
+
+impl<M: Monoid> HeapSize for AugmentedTreapSet<M> {
+    fn heap_size_bytes(&self) -> usize {
+        self.iter().count() * size_of::<Node<M>>() + self.cmp.heap_size_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::monoid::{Max, Sum};
+
+    use super::*;
+
+    #[test]
+    fn test_set_semantics() {
+        let mut treap = [1, 1, 2].into_iter().collect::<TreapSet>();
+        assert_eq!(vec![1, 2], treap.iter().copied().collect::<Vec<i64>>());
+        assert_eq!(treap, [2, 1].into_iter().collect());
+        assert!(!treap.insert(2));
+        let other = [2, 3, 3].into_iter().collect::<TreapSet>();
+        assert_eq!(
+            vec![1, 2, 3],
+            (&treap | &other).into_iter().collect::<Vec<i64>>()
+        );
+        assert_eq!(None, treap.remove(7));
+        assert_eq!(None, TreapSet::default().remove(7));
+        assert_eq!(Some(1), treap.remove(1));
+        assert_eq!(None, treap.remove(1));
+        assert_eq!(vec![2], treap.values());
+    }
+
+    #[test]
+    fn test_retain_extract() {
+        let mut treap = (0..1000).collect::<AugmentedTreapSet<Sum>>();
+        treap.retain(|x| x % 2 == 1);
+        // The rebuild lays keys out by position and sifts the priorities
+        // down, so the result is a valid treap rather than a perfect tree.
+        assert!(heap_ordered(&treap.root));
+        assert_eq!(500 * 500, treap.summary());
+        let extracted = treap.extract_if(|x| *x < 10).collect::<Vec<i64>>();
+        assert_eq!(vec![1, 3, 5, 7, 9], extracted);
+        assert!(heap_ordered(&treap.root));
+        assert!(treap.insert(4));
+        assert_eq!(Some(11), treap.remove(11));
+        assert_eq!(
+            vec![4, 13, 15],
+            treap.iter().take(3).copied().collect::<Vec<i64>>()
+        );
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_parallel_set_operations() {
+        use std::collections::BTreeSet;
+
+        use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+        let mut rng = rand::thread_rng();
+        let a = (0..50000)
+            .map(|_| rng.gen_range(0..100000))
+            .collect::<Vec<i64>>();
+        let b = (0..20000)
+            .map(|_| rng.gen_range(0..100000))
+            .collect::<Vec<i64>>();
+        let (a_set, b_set) = (
+            a.iter().copied().collect::<BTreeSet<i64>>(),
+            b.iter().copied().collect::<BTreeSet<i64>>(),
+        );
+        let treap = |values: &[i64]| {
+            values
+                .to_vec()
+                .into_par_iter()
+                .collect::<AugmentedTreapSet<Sum>>()
+        };
+        assert!(heap_ordered(&treap(&a).root));
+        for (result, expected) in [
+            (treap(&a).par_union(treap(&b)), &a_set | &b_set),
+            (treap(&a).par_intersection(treap(&b)), &a_set & &b_set),
+            (treap(&a).par_difference(treap(&b)), &a_set - &b_set),
+            (treap(&b).par_difference(treap(&a)), &b_set - &a_set),
+        ] {
+            assert!(heap_ordered(&result.root));
+            assert_eq!(expected.iter().sum::<i64>(), result.summary());
+            assert!(expected.into_iter().eq(result.into_iter()));
+        }
+        // A reversed `other` cannot be split by the keys of `self`.
+        let mut reversed = AugmentedTreapSet::<Sum>::with_comparator(|a, b| b.cmp(a));
+        reversed.extend(&b_set);
+        let union = treap(&a).par_union(reversed);
+        assert!((&a_set | &b_set).into_iter().eq(union.into_iter()));
+    }
+
+    fn heap_ordered<M: Monoid>(t: &Treap<M>) -> bool {
+        t.as_ref().is_none_or(|node| {
+            [&node.left, &node.right]
+                .into_iter()
+                .flatten()
+                .all(|child| child.priority <= node.priority)
+                && heap_ordered(&node.left)
+                && heap_ordered(&node.right)
+        })
+    }
+
+    #[test]
+    fn test_cursor() {
+        let mut treap = (1..=5).collect::<TreapSet>();
+        let mut cursor = treap.cursor();
+        cursor.seek(3);
+        cursor.move_next();
+        assert_eq!(Some(&4), cursor.peek());
+        cursor.move_next();
+        cursor.move_next();
+        assert_eq!(None, cursor.peek());
+        cursor.move_next();
+        assert_eq!(Some(&1), cursor.peek());
+
+        let mut cursor = treap.cursor_mut();
+        cursor.seek(2);
+        assert_eq!(Some(2), cursor.remove_current());
+        assert_eq!(Some(&3), cursor.peek());
+        cursor.move_prev();
+        assert_eq!(Some(&1), cursor.peek());
+        assert!(cursor.insert_after(3).is_err());
+        assert_eq!(Ok(()), cursor.insert_after(2));
+        assert_eq!(vec![1, 2, 3, 4, 5], treap.into_iter().collect::<Vec<i64>>());
+    }
+
+    #[test]
+    fn test_fold() {
+        let mut sums = AugmentedTreapSet::<Sum>::default();
+        let mut maxes = AugmentedTreapSet::<Max>::default();
+        for value in 1..=50 {
+            sums.insert(value);
+            maxes.insert(value);
+        }
+        sums.remove(25);
+        maxes.remove(50);
+        assert_eq!(1275 - 25, sums.summary());
+        assert_eq!((20..30).sum::<i64>() - 25, sums.fold(20..30));
+        assert_eq!(0, sums.fold(100..));
+        assert_eq!(Some(49), maxes.summary());
+        assert_eq!(Some(10), maxes.fold(..=10));
+        assert_eq!(None, maxes.fold(60..));
+    }
+}
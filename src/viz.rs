@@ -0,0 +1,212 @@
+use std::collections::HashMap;
+
+use crate::Tree;
+
+/// Binary tree reduced to node labels, ready to be drawn. `value` identifies
+/// the node, `label` is what gets drawn.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Shape {
+    pub value: i64,
+    pub label: String,
+    pub left: Option<Box<Shape>>,
+    pub right: Option<Box<Shape>>,
+}
+
+impl Shape {
+    pub fn new(value: i64, label: String, left: Option<Shape>, right: Option<Shape>) -> Self {
+        Self {
+            value,
+            label,
+            left: left.map(Box::new),
+            right: right.map(Box::new),
+        }
+    }
+
+    /// The same tree with the labels of `values` in brackets.
+    pub fn highlighted(&self, values: &[i64]) -> Shape {
+        let highlight = |child: &Option<Box<Shape>>| {
+            child
+                .as_deref()
+                .map(|child| Box::new(child.highlighted(values)))
+        };
+        Shape {
+            value: self.value,
+            label: if values.contains(&self.value) {
+                format!("[{}]", self.label)
+            } else {
+                self.label.clone()
+            },
+            left: highlight(&self.left),
+            right: highlight(&self.right),
+        }
+    }
+
+    fn parents(&self, parent: Option<i64>, parents: &mut HashMap<i64, Option<i64>>) {
+        parents.insert(self.value, parent);
+        for child in [&self.left, &self.right].into_iter().flatten() {
+            child.parents(Some(self.value), parents);
+        }
+    }
+}
+
+/// Values that are in both trees but under a different parent, i.e. the
+/// nodes that rotations or merges moved. Sorted.
+pub fn moved(before: &Shape, after: &Shape) -> Vec<i64> {
+    let mut old = HashMap::new();
+    before.parents(None, &mut old);
+    let mut new = HashMap::new();
+    after.parents(None, &mut new);
+    let mut moved = new
+        .into_iter()
+        .filter(|(value, parent)| old.get(value).is_some_and(|old| old != parent))
+        .map(|(value, _)| value)
+        .collect::<Vec<_>>();
+    moved.sort_unstable();
+    moved
+}
+
+/// `label:priority` with a treap priority scaled down to `0..100`.
+pub(crate) fn priority_label(label: String, priority: usize) -> String {
+    format!("{label}:{}", priority / (usize::MAX / 100 + 1))
+}
+
+// Where a node ends up on the canvas: labels never share a column because
+// every node gets its own slot in key order.
+struct Placed<'a> {
+    label: &'a str,
+    depth: usize,
+    start: usize,
+    left_center: Option<usize>,
+    right_center: Option<usize>,
+}
+
+impl Placed<'_> {
+    fn center(&self) -> usize {
+        self.start + self.label.len() / 2
+    }
+
+    fn end(&self) -> usize {
+        self.start + self.label.len()
+    }
+}
+
+// Returns the index of `shape` in `placed`.
+fn place<'a>(
+    shape: &'a Shape,
+    depth: usize,
+    next: &mut usize,
+    placed: &mut Vec<Placed<'a>>,
+) -> usize {
+    let left = shape
+        .left
+        .as_deref()
+        .map(|left| place(left, depth + 1, next, placed));
+    let index = placed.len();
+    placed.push(Placed {
+        label: &shape.label,
+        depth,
+        start: *next,
+        left_center: None,
+        right_center: None,
+    });
+    *next += shape.label.len() + 1;
+    let right = shape
+        .right
+        .as_deref()
+        .map(|right| place(right, depth + 1, next, placed));
+    placed[index].left_center = left.map(|left| placed[left].center());
+    placed[index].right_center = right.map(|right| placed[right].center());
+    index
+}
+
+/// Draws the tree top-down with `/` and `\` between parents and children:
+///
+/// ```text
+///     __4__
+///    /     \
+///   2       6
+///  / \     / \
+/// 1   3   5   7
+/// ```
+pub fn render(shape: &Shape) -> String {
+    let mut placed = vec![];
+    place(shape, 0, &mut 0, &mut placed);
+    let width = placed.iter().map(Placed::end).max().unwrap_or(0);
+    let depth = placed.iter().map(|node| node.depth).max().unwrap_or(0);
+    let mut canvas = vec![vec![' '; width]; 2 * depth + 1];
+    for node in &placed {
+        let row = 2 * node.depth;
+        for (column, ch) in node.label.chars().enumerate() {
+            canvas[row][node.start + column] = ch;
+        }
+        if let Some(left) = node.left_center {
+            canvas[row + 1][left + 1] = '/';
+            canvas[row][left + 2..node.start].fill('_');
+        }
+        if let Some(right) = node.right_center {
+            canvas[row + 1][right - 1] = '\\';
+            canvas[row][node.end()..right - 1].fill('_');
+        }
+    }
+    canvas
+        .into_iter()
+        .map(|row| row.into_iter().collect::<String>().trim_end().to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// `render` for any tree, falling back to the list of values for structures
+/// without a shape.
+pub fn draw(tree: &dyn Tree, highlight: &[i64]) -> String {
+    match tree.shape() {
+        Some(shape) => render(&shape.highlighted(highlight)),
+        None if tree.values().is_empty() => "(empty)".to_string(),
+        None => format!("{:?}", tree.values()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(value: i64, left: Option<Shape>, right: Option<Shape>) -> Option<Shape> {
+        Some(Shape::new(value, value.to_string(), left, right))
+    }
+
+    fn leaf(value: i64) -> Option<Shape> {
+        node(value, None, None)
+    }
+
+    #[test]
+    fn test_render() {
+        let shape = node(4, node(2, leaf(1), leaf(3)), node(16, None, leaf(100))).unwrap();
+        assert_eq!(
+            [
+                "    __4_",
+                "   /    \\",
+                "  2     16_",
+                " / \\       \\",
+                "1   3      100",
+            ]
+            .join("\n"),
+            render(&shape)
+        );
+        assert_eq!("7", render(&leaf(7).unwrap()));
+    }
+
+    #[test]
+    fn test_moved() {
+        // Right rotation around 3 after inserting 1.
+        let before = node(3, node(2, leaf(1), None), None).unwrap();
+        let after = node(2, leaf(1), leaf(3)).unwrap();
+        assert_eq!(vec![2, 3], moved(&before, &after));
+        let grown = node(2, leaf(1), node(3, None, leaf(4))).unwrap();
+        assert!(moved(&after, &grown).is_empty());
+        assert_eq!(
+            "  [2]_\n /    \\\n1     [3]",
+            render(&after.highlighted(&[2, 3]))
+        );
+        assert_eq!("5:0", priority_label("5".to_string(), 0));
+        assert_eq!("5:99", priority_label("5".to_string(), usize::MAX));
+    }
+}
@@ -0,0 +1,624 @@
+use std::{
+    fmt,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rand_distr::{Distribution, Zipf};
+
+use crate::{
+    histogram::LatencyHistogram,
+    memory::{self, MemoryUsage},
+    Tree,
+};
+
+/// How keys are drawn from the universe `0..universe`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeyDistribution {
+    Uniform,
+    /// 0, 1, 2, ... wrapping around at the end of the universe.
+    Sequential,
+    /// The universe from its top key down.
+    Reverse,
+    /// Key `k` is drawn with weight `1 / (k + 1)^exponent`, so small keys are hot.
+    Zipf {
+        exponent: f64,
+    },
+    /// Keys gather in `clusters` evenly spaced groups, each `width` keys wide.
+    Clustered {
+        clusters: u64,
+        width: u64,
+    },
+    /// Ascending runs of `period` keys, each run starting half a period
+    /// further than the previous one.
+    Sawtooth {
+        period: u64,
+    },
+}
+
+impl KeyDistribution {
+    /// Fails for a Zipf exponent that is negative or not finite.
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            KeyDistribution::Zipf { exponent } if !(exponent.is_finite() && *exponent >= 0.0) => {
+                Err(format!(
+                    "Zipf exponent must be finite and not negative, got {exponent}"
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for KeyDistribution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyDistribution::Uniform => f.write_str("uniform"),
+            KeyDistribution::Sequential => f.write_str("sequential"),
+            KeyDistribution::Reverse => f.write_str("reverse"),
+            KeyDistribution::Zipf { exponent } => write!(f, "zipf-{exponent}"),
+            KeyDistribution::Clustered { clusters, width } => {
+                write!(f, "clustered-{clusters}x{width}")
+            }
+            KeyDistribution::Sawtooth { period } => write!(f, "sawtooth-{period}"),
+        }
+    }
+}
+
+/// Parses what `Display` prints. Parameters may be left out: `zipf` is
+/// `zipf-1`, `clustered` is `clustered-16x1000` and `sawtooth` is
+/// `sawtooth-1000`.
+impl FromStr for KeyDistribution {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, parameters) = s.split_once('-').unwrap_or((s, ""));
+        fn parse<T: FromStr>(field: &str, default: T) -> Result<T, String> {
+            match field {
+                "" => Ok(default),
+                field => field.parse().map_err(|_| format!("cannot parse {field:?}")),
+            }
+        }
+        match name {
+            "uniform" => Ok(KeyDistribution::Uniform),
+            "sequential" => Ok(KeyDistribution::Sequential),
+            "reverse" => Ok(KeyDistribution::Reverse),
+            "zipf" => {
+                let distribution = KeyDistribution::Zipf {
+                    exponent: parse(parameters, 1.0)?,
+                };
+                distribution.validate()?;
+                Ok(distribution)
+            }
+            "clustered" => {
+                let (clusters, width) = parameters.split_once('x').unwrap_or((parameters, ""));
+                Ok(KeyDistribution::Clustered {
+                    clusters: parse(clusters, 16)?,
+                    width: parse(width, 1000)?,
+                })
+            }
+            "sawtooth" => Ok(KeyDistribution::Sawtooth {
+                period: parse(parameters, 1000)?,
+            }),
+            _ => Err(format!("unknown key distribution {s:?}")),
+        }
+    }
+}
+
+/// Relative weights of the operations. Only the ratios matter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpMix {
+    pub search: u32,
+    pub insert: u32,
+    pub remove: u32,
+}
+
+/// Prints the weights as `search:insert:remove`.
+impl fmt::Display for OpMix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.search, self.insert, self.remove)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Schedule {
+    /// All inserts, then all searches, then all removes.
+    Phased,
+    /// Operations shuffled together according to the mix.
+    Interleaved,
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Schedule::Phased => "phased",
+            Schedule::Interleaved => "interleaved",
+        })
+    }
+}
+
+impl FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "phased" => Ok(Schedule::Phased),
+            "interleaved" => Ok(Schedule::Interleaved),
+            _ => Err(format!("unknown schedule {s:?}")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Workload {
+    pub distribution: KeyDistribution,
+    /// Where searches and removes draw their keys. `None` draws them the
+    /// same way as inserts.
+    pub lookups: Option<KeyDistribution>,
+    pub mix: OpMix,
+    pub schedule: Schedule,
+    /// Total number of operations of all kinds.
+    pub operations: usize,
+    pub universe: u64,
+    pub seed: u64,
+}
+
+impl Workload {
+    /// `n + n / 10 * 2` operations on uniform keys from `0..3n`, split
+    /// 1:10:1 between searches, inserts and removes, each count rounded down:
+    /// about `n` inserts, then `n / 10` searches and `n / 10` removes.
+    pub fn random(n: usize) -> Self {
+        Self {
+            distribution: KeyDistribution::Uniform,
+            lookups: None,
+            mix: OpMix {
+                search: 1,
+                insert: 10,
+                remove: 1,
+            },
+            schedule: Schedule::Phased,
+            operations: n + n / 10 * 2,
+            universe: 3 * n as u64,
+            seed: rand::thread_rng().gen(),
+        }
+    }
+
+    /// Like `random`, but inserts go in sorted order and searches and removes
+    /// draw uniform keys from `0..n`.
+    pub fn sorted(n: usize) -> Self {
+        Self {
+            distribution: KeyDistribution::Sequential,
+            lookups: Some(KeyDistribution::Uniform),
+            universe: n as u64,
+            ..Self::random(n)
+        }
+    }
+
+    /// Short name such as `zipf-1.2/interleaved`, or `sequential+uniform/phased`
+    /// when lookups follow another distribution.
+    pub fn name(&self) -> String {
+        match self.lookups {
+            Some(lookups) => format!("{}+{lookups}/{}", self.distribution, self.schedule),
+            None => format!("{}/{}", self.distribution, self.schedule),
+        }
+    }
+
+    /// `name` together with the mix and the universe, such as
+    /// `uniform/phased/mix-1:10:1/universe-3000`. Runs with the same key did
+    /// the same kind of work, whatever their seeds.
+    pub fn key(&self) -> String {
+        format!(
+            "{}/mix-{}/universe-{}",
+            self.name(),
+            self.mix,
+            self.universe
+        )
+    }
+
+    /// Fails if a key distribution cannot be sampled. A workload built by
+    /// hand should pass this before it is run.
+    pub fn validate(&self) -> Result<(), String> {
+        self.distribution.validate()?;
+        self.lookups
+            .as_ref()
+            .map_or(Ok(()), KeyDistribution::validate)
+    }
+
+    fn distribution_of(&self, operation: Operation) -> KeyDistribution {
+        match operation {
+            Operation::Insert => self.distribution,
+            Operation::Search | Operation::Remove => self.lookups.unwrap_or(self.distribution),
+        }
+    }
+
+    /// Operations of each kind: `[search, insert, remove]`.
+    fn counts(&self) -> [usize; 3] {
+        let OpMix {
+            search,
+            insert,
+            remove,
+        } = self.mix;
+        let total = (search + insert + remove).max(1) as usize;
+        [search, insert, remove].map(|weight| self.operations * weight as usize / total)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operation {
+    Search,
+    Insert,
+    Remove,
+}
+
+impl Operation {
+    pub const ALL: [Operation; 3] = [Operation::Search, Operation::Insert, Operation::Remove];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Operation::Search => "search",
+            Operation::Insert => "insert",
+            Operation::Remove => "remove",
+        })
+    }
+}
+
+// Endless stream of keys following one distribution.
+struct Keys {
+    distribution: KeyDistribution,
+    universe: u64,
+    zipf: Option<Zipf<f64>>,
+    step: u64,
+}
+
+impl Keys {
+    fn new(distribution: KeyDistribution, universe: u64) -> Self {
+        let universe = universe.max(1);
+        let zipf = match distribution {
+            KeyDistribution::Zipf { exponent } => Some(
+                Zipf::new(universe, exponent).expect("the workload should have been validated"),
+            ),
+            _ => None,
+        };
+        Self {
+            distribution,
+            universe,
+            zipf,
+            step: 0,
+        }
+    }
+
+    fn next(&mut self, rng: &mut StdRng) -> i64 {
+        let step = self.step;
+        self.step += 1;
+        let key = match self.distribution {
+            KeyDistribution::Uniform => rng.gen_range(0..self.universe),
+            KeyDistribution::Sequential => step,
+            KeyDistribution::Reverse => self.universe - 1 - step % self.universe,
+            KeyDistribution::Zipf { .. } => self.zipf.as_ref().unwrap().sample(rng) as u64 - 1,
+            KeyDistribution::Clustered { clusters, width } => {
+                let clusters = clusters.max(1);
+                let center = rng.gen_range(0..clusters) * (self.universe / clusters);
+                center + rng.gen_range(0..width.max(1))
+            }
+            KeyDistribution::Sawtooth { period } => {
+                let period = period.max(1);
+                step / period * period.div_ceil(2) + step % period
+            }
+        };
+        (key % self.universe) as i64
+    }
+}
+
+/// Counts for one kind of operation. `succeeded` is how many inserts and
+/// removes changed the tree and how many searches found the key.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct OpCount {
+    pub attempted: usize,
+    pub succeeded: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Only(Operation),
+    Mixed,
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Phase::Only(operation) => operation.fmt(f),
+            Phase::Mixed => f.write_str("mixed"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PhaseResult {
+    pub phase: Phase,
+    pub elapsed: Duration,
+    counts: [OpCount; 3],
+    latencies: [LatencyHistogram; 3],
+}
+
+impl PhaseResult {
+    fn new(phase: Phase) -> Self {
+        Self {
+            phase,
+            elapsed: Duration::ZERO,
+            counts: Default::default(),
+            latencies: Default::default(),
+        }
+    }
+
+    fn record(&mut self, operation: Operation, succeeded: bool, latency: Duration) {
+        let count = &mut self.counts[operation.index()];
+        count.attempted += 1;
+        count.succeeded += usize::from(succeeded);
+        self.latencies[operation.index()].record(latency);
+    }
+
+    pub fn count(&self, operation: Operation) -> OpCount {
+        self.counts[operation.index()]
+    }
+
+    pub fn latency(&self, operation: Operation) -> &LatencyHistogram {
+        &self.latencies[operation.index()]
+    }
+
+    pub fn operations(&self) -> usize {
+        self.counts.iter().map(|count| count.attempted).sum()
+    }
+
+    /// Zero for an empty phase.
+    pub fn ops_per_sec(&self) -> f64 {
+        match self.elapsed.as_secs_f64() {
+            seconds if seconds > 0.0 => self.operations() as f64 / seconds,
+            _ => 0.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkloadResult {
+    pub workload: Workload,
+    pub phases: Vec<PhaseResult>,
+    /// Allocations made by the tree during the run. Known only when
+    /// `memory::CountingAllocator` is installed.
+    pub memory: Option<MemoryUsage>,
+}
+
+impl WorkloadResult {
+    /// Counts of `operation` over all phases.
+    pub fn count(&self, operation: Operation) -> OpCount {
+        self.phases.iter().map(|phase| phase.count(operation)).fold(
+            OpCount::default(),
+            |total, count| OpCount {
+                attempted: total.attempted + count.attempted,
+                succeeded: total.succeeded + count.succeeded,
+            },
+        )
+    }
+
+    /// Latencies of `operation` over all phases.
+    pub fn latency(&self, operation: Operation) -> LatencyHistogram {
+        let mut histogram = LatencyHistogram::default();
+        for phase in &self.phases {
+            histogram.merge(phase.latency(operation));
+        }
+        histogram
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.phases.iter().map(|phase| phase.elapsed).sum()
+    }
+}
+
+pub(crate) fn apply(tree: &mut dyn Tree, operation: Operation, key: i64) -> bool {
+    match operation {
+        Operation::Search => tree.search(key),
+        Operation::Insert => tree.insert(key),
+        Operation::Remove => match tree.remove(key) {
+            Some(removed) => {
+                assert_eq!(key, removed, "tree removed a different key");
+                true
+            }
+            None => false,
+        },
+    }
+}
+
+/// Runs `workload` against `tree`. Every kind of operation draws keys from
+/// its own stream, so sequential searches start over from the first key.
+///
+/// Keys are generated on the fly and result buffers are allocated up front,
+/// so the memory usage covers only what the tree asked for. Every operation is
+/// timed on its own for the latency histograms.
+///
+/// # Panics
+///
+/// If `workload` does not pass [`Workload::validate`].
+pub fn run(tree: &mut dyn Tree, workload: &Workload) -> WorkloadResult {
+    let mut rng = StdRng::seed_from_u64(workload.seed);
+    let mut keys = Operation::ALL
+        .map(|operation| Keys::new(workload.distribution_of(operation), workload.universe));
+    let mut left = workload.counts();
+    let mut phases = match workload.schedule {
+        Schedule::Phased => [Operation::Insert, Operation::Search, Operation::Remove]
+            .map(|operation| PhaseResult::new(Phase::Only(operation)))
+            .to_vec(),
+        Schedule::Interleaved => vec![PhaseResult::new(Phase::Mixed)],
+    };
+    let baseline = memory::start_tracking();
+    for result in &mut phases {
+        let start = Instant::now();
+        // Drawing without replacement keeps the exact counts of the mix.
+        let mut remaining = match result.phase {
+            Phase::Only(operation) => left[operation.index()],
+            Phase::Mixed => left.iter().sum(),
+        };
+        while remaining > 0 {
+            let operation = match result.phase {
+                Phase::Only(operation) => operation,
+                Phase::Mixed => {
+                    let mut pick = rng.gen_range(0..remaining);
+                    Operation::ALL
+                        .into_iter()
+                        .find(|operation| {
+                            let left = left[operation.index()];
+                            if pick < left {
+                                return true;
+                            }
+                            pick -= left;
+                            false
+                        })
+                        .unwrap()
+                }
+            };
+            left[operation.index()] -= 1;
+            remaining -= 1;
+            let key = keys[operation.index()].next(&mut rng);
+            let op_start = Instant::now();
+            let succeeded = apply(tree, operation, key);
+            result.record(operation, succeeded, op_start.elapsed());
+        }
+        result.elapsed = start.elapsed();
+    }
+    WorkloadResult {
+        workload: workload.clone(),
+        phases,
+        memory: memory::usage_since(baseline),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use crate::avl::AvlSet;
+
+    use super::*;
+
+    fn keys(distribution: KeyDistribution, universe: u64, n: usize) -> Vec<i64> {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut keys = Keys::new(distribution, universe);
+        (0..n).map(|_| keys.next(&mut rng)).collect()
+    }
+
+    #[test]
+    fn test_distributions() {
+        assert_eq!(vec![0, 1, 2, 0], keys(KeyDistribution::Sequential, 3, 4));
+        assert_eq!(vec![2, 1, 0, 2], keys(KeyDistribution::Reverse, 3, 4));
+        assert_eq!(
+            vec![0, 1, 2, 2, 3, 4, 4, 5],
+            keys(KeyDistribution::Sawtooth { period: 3 }, 100, 8)
+        );
+        let zipf = keys(KeyDistribution::Zipf { exponent: 1.2 }, 1000, 10000);
+        let hot = zipf.iter().filter(|key| **key < 10).count();
+        assert!(hot > 5000, "{hot}");
+        let clustered = keys(
+            KeyDistribution::Clustered {
+                clusters: 4,
+                width: 10,
+            },
+            1000,
+            1000,
+        );
+        assert!(clustered.iter().all(|key| key % 250 < 10));
+        assert!(keys(KeyDistribution::Uniform, 50, 1000)
+            .iter()
+            .all(|key| (0..50).contains(key)));
+    }
+
+    #[test]
+    fn test_parse_names() {
+        for distribution in [
+            KeyDistribution::Uniform,
+            KeyDistribution::Reverse,
+            KeyDistribution::Zipf { exponent: 1.2 },
+            KeyDistribution::Clustered {
+                clusters: 4,
+                width: 10,
+            },
+            KeyDistribution::Sawtooth { period: 3 },
+        ] {
+            assert_eq!(Ok(distribution), distribution.to_string().parse());
+        }
+        assert_eq!(Ok(KeyDistribution::Zipf { exponent: 1.0 }), "zipf".parse());
+        assert_eq!(
+            Ok(KeyDistribution::Clustered {
+                clusters: 8,
+                width: 1000
+            }),
+            "clustered-8".parse()
+        );
+        assert!("zipf-hot".parse::<KeyDistribution>().is_err());
+        assert!("zipf--1".parse::<KeyDistribution>().is_err());
+        assert!("zipf-NaN".parse::<KeyDistribution>().is_err());
+        assert!("zipf-inf".parse::<KeyDistribution>().is_err());
+        assert!("normal".parse::<KeyDistribution>().is_err());
+        let workload = Workload {
+            lookups: Some(KeyDistribution::Zipf { exponent: -1.0 }),
+            ..Workload::random(10)
+        };
+        assert!(workload.validate().is_err());
+        assert_eq!(Ok(()), Workload::random(10).validate());
+        assert_eq!(Ok(Schedule::Interleaved), "interleaved".parse());
+    }
+
+    #[test]
+    fn test_run() {
+        let workload = Workload::sorted(1000);
+        let result = run(&mut AvlSet::default(), &workload);
+        let phases = result
+            .phases
+            .iter()
+            .map(|phase| (phase.phase, phase.operations()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                (Phase::Only(Operation::Insert), 1000),
+                (Phase::Only(Operation::Search), 100),
+                (Phase::Only(Operation::Remove), 100)
+            ],
+            phases
+        );
+        // Lookups are uniform over the sorted keys, not a replay of them.
+        assert_eq!(100, result.count(Operation::Search).succeeded);
+        let expected = run(&mut BTreeSet::new(), &workload);
+        assert_eq!(
+            expected.count(Operation::Remove),
+            result.count(Operation::Remove)
+        );
+        assert_eq!(1000, result.latency(Operation::Insert).len());
+        assert!(result.phases[1].latency(Operation::Insert).is_empty());
+
+        // The same seed replays the same operations on any tree.
+        let workload = Workload {
+            distribution: KeyDistribution::Zipf { exponent: 0.9 },
+            schedule: Schedule::Interleaved,
+            mix: OpMix {
+                search: 2,
+                insert: 2,
+                remove: 1,
+            },
+            ..Workload::random(5000)
+        };
+        let mut tree = AvlSet::default();
+        let mut oracle = BTreeSet::new();
+        let result = run(&mut tree, &workload);
+        let expected = run(&mut oracle, &workload);
+        assert_eq!(1, result.phases.len());
+        assert_eq!(2400, result.count(Operation::Insert).attempted);
+        for operation in Operation::ALL {
+            assert_eq!(expected.count(operation), result.count(operation));
+        }
+        assert!(tree.iter().eq(oracle.iter()));
+    }
+}